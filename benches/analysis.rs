@@ -0,0 +1,70 @@
+//! Benchmarks of the analysis on a large synthetic dataset, run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use lyagushka::{generate_clusters, Anomaly, AnomalyIndex, Lyagushka, SearchConfig, Value};
+
+/// A sorted dataset of 2000 clusters of 50 points each.
+// The generator produces `i32`, which is `Value` itself unless built with `bigint`.
+#[allow(clippy::useless_conversion)]
+fn dataset() -> Vec<Value> {
+    generate_clusters(2000, 50, 20, 500, 1).expect("the parameters are valid").into_iter().map(Value::from).collect()
+}
+
+/// Compares an instance growing its anomaly storage as needed with one created with
+/// [`Lyagushka::with_capacity`] for the number of anomalies the analysis finds.
+fn preallocation(c: &mut Criterion) {
+    let dataset: Vec<Value> = dataset();
+    let config: SearchConfig = SearchConfig::new(1.5, 6);
+    let mut probe: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+    probe.analyze(&config);
+    let capacity: usize = probe.anomalies().len();
+
+    let mut group = c.benchmark_group("preallocation");
+    group.bench_function("new", |b| {
+        b.iter_batched(|| Lyagushka::new(dataset.clone()).unwrap(), |mut zhaba| zhaba.analyze(black_box(&config)), BatchSize::LargeInput)
+    });
+    group.bench_function("with_capacity", |b| {
+        b.iter_batched(|| Lyagushka::with_capacity(dataset.clone(), capacity).unwrap(), |mut zhaba| zhaba.analyze(black_box(&config)), BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+/// Compares the general path, which sorts the dataset and collects the statistics after the
+/// scan, with the fused path of `assume_sorted`, which accumulates them during the scan.
+fn assume_sorted(c: &mut Criterion) {
+    let dataset: Vec<Value> = dataset();
+    let mut group = c.benchmark_group("assume_sorted");
+    for (name, assume_sorted) in [("general", false), ("fused", true)] {
+        let mut config: SearchConfig = SearchConfig::new(1.5, 6);
+        config.assume_sorted = assume_sorted;
+        group.bench_function(name, |b| {
+            b.iter_batched(|| Lyagushka::new(dataset.clone()).unwrap(), |mut zhaba| zhaba.analyze(black_box(&config)), BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+/// Compares containment queries at every thousandth point of the dataset answered by an
+/// [`AnomalyIndex`] with a linear search over the anomalies.
+fn index(c: &mut Criterion) {
+    let mut zhaba: Lyagushka = Lyagushka::new(dataset()).unwrap();
+    zhaba.analyze(&SearchConfig::new(1.5, 6));
+    let index: AnomalyIndex = zhaba.index();
+    let positions: Vec<Value> = zhaba.dataset().iter().step_by(1000).copied().collect();
+
+    let mut group = c.benchmark_group("index");
+    group.bench_function("linear", |b| {
+        b.iter(|| {
+            positions.iter()
+                .map(|&value: &Value| zhaba.anomalies().iter().filter(|info: &&Anomaly| info.start <= value && value <= info.end).count())
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("index", |b| {
+        b.iter(|| positions.iter().map(|&value: &Value| index.query(black_box(value)).len()).sum::<usize>())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, preallocation, assume_sorted, index);
+criterion_main!(benches);
@@ -0,0 +1,316 @@
+//! The `no_std` core of lyagushka: the thresholds, the scan for clusters and gaps and the
+//! statistics they are scored by, for targets such as microcontrollers without the standard
+//! library.
+//!
+//! Nothing here allocates. The scan reports what it finds to a callback instead of
+//! collecting it, and all functions work on borrowed slices of a sorted dataset.
+#![no_std]
+
+/// Integer type of the dataset values and of all span arithmetic.
+///
+/// With the `bigint` feature this is `i128`, so that spans between values near the limits of
+/// `i64` cannot overflow. Statistics are always calculated in `f32`; values and spans beyond
+/// its 24-bit mantissa are rounded to the nearest representable float, which loses precision
+/// in densities and z-scores but never in the reported `start`, `end` and `span_length`.
+#[cfg(not(feature = "bigint"))]
+pub type Value = i32;
+#[cfg(feature = "bigint")]
+pub type Value = i128;
+
+/// Thresholds derived from the mean distance between consecutive points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    pub mean_distance: f32,
+    pub cluster_threshold: f32,
+    pub cluster_exit_threshold: f32,
+    pub gap_threshold: f32,
+}
+
+/// Options of a [`scan`].
+#[derive(Debug, Clone, Copy)]
+pub struct ScanOptions {
+    pub thresholds: Thresholds,
+    /// Fewest points a cluster needs to be reported rather than discarded.
+    pub min_cluster_size: usize,
+    /// Join points into a cluster when their distance equals the cluster threshold (`<=` instead of `<`).
+    pub inclusive_cluster_threshold: bool,
+    /// Record a gap when its span equals the gap threshold (`>=` instead of `>`).
+    pub inclusive_gap_threshold: bool,
+    /// Report distances that neither join a cluster nor exceed the gap threshold as dead zones.
+    pub report_dead_zones: bool,
+    /// Report the stretches between the dataset bounds and the first and last anomaly as edge
+    /// gaps when they are wider than the gap threshold.
+    pub edge_gaps: bool,
+}
+
+/// A finding of a [`scan`], in terms of sorted positions in the dataset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    /// The points at the positions `first..=last` form a cluster.
+    Cluster { first: usize, last: usize },
+    /// The points at the positions `first..=last` would have formed a cluster, but were fewer
+    /// than `min_cluster_size`.
+    Discarded { first: usize, last: usize },
+    /// The distance between the points at `position` and `position + 1` is a gap.
+    Gap { position: usize },
+    /// The distance between the points at `position` and `position + 1` lies between the
+    /// cluster and gap thresholds.
+    DeadZone { position: usize },
+    /// The sparse stretch from `start` to `end` between a dataset bound and the first or last
+    /// anomaly.
+    EdgeGap { start: Value, end: Value },
+}
+
+/// Whether the sorted dataset consists of two or more copies of a single value.
+pub fn is_zero_range(dataset: &[Value]) -> bool {
+    dataset.len() >= 2 && dataset.first() == dataset.last()
+}
+
+/// Derives the clustering and gap thresholds for the sorted dataset, leaving out the distances
+/// between consecutive points for which `masked` holds.
+///
+/// The mean distance is zero without any consecutive points, as in an empty dataset. In
+/// `adaptive` mode, the distances a first pass would flag as gaps are excluded from it, so
+/// that a few very large gaps don't inflate the thresholds and hide moderate ones.
+pub fn thresholds<M>(dataset: &[Value], factor: f32, enter_factor: Option<f32>, exit_factor: Option<f32>, adaptive: bool, masked: M) -> Thresholds
+where
+    M: Fn(Value, Value) -> bool,
+{
+    let distances = || dataset.windows(2)
+        .filter(|w: &&[Value]| !masked(w[0], w[1]))
+        .map(|w: &[Value]| (w[1] - w[0]) as f32);
+    let mean = |(sum, count): (f32, usize)| if count == 0 { None } else { Some(sum / count as f32) };
+    let total = |(sum, count): (f32, usize), distance: f32| (sum + distance, count + 1);
+
+    let mut mean_distance: f32 = mean(distances().fold((0.0, 0), total)).unwrap_or(0.0);
+    if adaptive {
+        let first_pass_gap_threshold: f32 = factor * mean_distance;
+        let baseline: (f32, usize) = distances()
+            .filter(|distance: &f32| *distance <= first_pass_gap_threshold)
+            .fold((0.0, 0), total);
+        mean_distance = mean(baseline).unwrap_or(mean_distance);
+    }
+
+    // With hysteresis, a cluster has to start below the (tighter) entry threshold but may then
+    // continue as long as the distances stay below the (looser) exit threshold.
+    Thresholds {
+        mean_distance,
+        cluster_threshold: mean_distance / enter_factor.unwrap_or(factor),
+        cluster_exit_threshold: mean_distance / exit_factor.unwrap_or(factor),
+        gap_threshold: factor * mean_distance,
+    }
+}
+
+/// Scans the sorted dataset for clusters and gaps and passes each finding to `emit`, in the
+/// order of the dataset and followed by the edge gaps. Distances for which `masked` holds end
+/// a cluster but are never reported.
+///
+/// A dataset of identical values has no distances to tell apart, so whatever the thresholds
+/// it forms a single zero-span cluster (if large enough) and nothing else.
+pub fn scan<M, E>(dataset: &[Value], options: &ScanOptions, masked: M, mut emit: E)
+where
+    M: Fn(Value, Value) -> bool,
+    E: FnMut(Segment),
+{
+    let ScanOptions { thresholds, min_cluster_size, .. } = *options;
+    let cluster = |first: usize, last: usize| if last - first + 1 >= min_cluster_size {
+        Segment::Cluster { first, last }
+    } else {
+        Segment::Discarded { first, last }
+    };
+    if is_zero_range(dataset) {
+        emit(cluster(0, dataset.len() - 1));
+        return;
+    }
+
+    // The sorted position of the first point of the current cluster, and the start of the
+    // first and the end of the last reported anomaly.
+    let mut cluster_start: Option<usize> = None;
+    let mut bounds: Option<(Value, Value)> = None;
+    let mut report = |segment: Segment, start: Value, end: Value| {
+        if !matches!(segment, Segment::Discarded { .. }) {
+            bounds = Some((bounds.map_or(start, |(first, _): (Value, Value)| first), end));
+        }
+        emit(segment);
+    };
+
+    for (position, window) in dataset.windows(2).enumerate() {
+        let gap_size: f32 = (window[1] - window[0]) as f32;
+
+        let threshold: f32 = if cluster_start.is_none() { thresholds.cluster_threshold } else { thresholds.cluster_exit_threshold };
+        let joins_cluster: bool = if options.inclusive_cluster_threshold {
+            gap_size <= threshold
+        } else {
+            gap_size < threshold
+        };
+        let is_gap: bool = if options.inclusive_gap_threshold {
+            gap_size >= thresholds.gap_threshold
+        } else {
+            gap_size > thresholds.gap_threshold
+        };
+
+        if joins_cluster {
+            cluster_start.get_or_insert(position);
+        } else {
+            // End the current cluster, then record the gap, or the dead-zone distance between
+            // the two thresholds if requested, unless it reaches into a known sparse region.
+            if let Some(first) = cluster_start.take() {
+                report(cluster(first, position), dataset[first], window[0]);
+            }
+            if masked(window[0], window[1]) {
+                continue;
+            } else if is_gap {
+                report(Segment::Gap { position }, window[0], window[1]);
+            } else if options.report_dead_zones {
+                report(Segment::DeadZone { position }, window[0], window[1]);
+            }
+        }
+    }
+
+    // Finalize the last cluster if applicable
+    if let Some(first) = cluster_start {
+        report(cluster(first, dataset.len() - 1), dataset[first], dataset[dataset.len() - 1]);
+    }
+
+    if let (true, Some((head_end, tail_start))) = (options.edge_gaps, bounds) {
+        let (dataset_min, dataset_max): (Value, Value) = (dataset[0], dataset[dataset.len() - 1]);
+        for (start, end) in [(dataset_min, head_end), (tail_start, dataset_max)] {
+            if (end - start) as f32 > thresholds.gap_threshold && !masked(start, end) {
+                emit(Segment::EdgeGap { start, end });
+            }
+        }
+    }
+}
+
+/// Returns the mean and population variance of `values`, both NaN if there are none.
+///
+/// Uses Welford's online algorithm, which needs a single pass and, by updating the mean and the
+/// sum of squared deviations together, avoids the cancellation of large, nearly equal terms.
+pub fn mean_and_variance(values: &[f32]) -> (f32, f32) {
+    let mut statistics: RunningStatistics = RunningStatistics::new();
+    for &value in values {
+        statistics.push(value);
+    }
+    statistics.mean_and_variance()
+}
+
+/// The mean and variance of a stream of values, updated one value at a time with Welford's
+/// online algorithm.
+///
+/// This is what [`mean_and_variance`] computes over a slice, so that values can be accumulated
+/// as a [`scan`] emits them and without storing them; pushed in the same order, both give
+/// identical results.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RunningStatistics {
+    count: usize,
+    mean: f32,
+    squared_deviations: f32,
+}
+
+impl RunningStatistics {
+    pub const fn new() -> Self {
+        RunningStatistics { count: 0, mean: 0.0, squared_deviations: 0.0 }
+    }
+
+    pub fn push(&mut self, value: f32) {
+        self.count += 1;
+        let delta: f32 = value - self.mean;
+        self.mean += delta / self.count as f32;
+        self.squared_deviations += delta * (value - self.mean);
+    }
+
+    /// The number of values pushed so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the mean and population variance of the values pushed so far, both NaN if there
+    /// are none.
+    pub fn mean_and_variance(&self) -> (f32, f32) {
+        if self.count == 0 {
+            return (f32::NAN, f32::NAN);
+        }
+        (self.mean, self.squared_deviations / self.count as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The two-pass computation that `mean_and_variance` replaced, in `f64` as a reference.
+    fn two_pass(values: &[f32]) -> (f64, f64) {
+        let mean: f64 = values.iter().map(|&value| value as f64).sum::<f64>() / values.len() as f64;
+        let variance: f64 = values.iter().map(|&value| (value as f64 - mean).powi(2)).sum::<f64>() / values.len() as f64;
+        (mean, variance)
+    }
+
+    fn assert_close(values: &[f32], tolerance: f64) {
+        let (mean, variance) = mean_and_variance(values);
+        let (expected_mean, expected_variance) = two_pass(values);
+        assert!((mean as f64 - expected_mean).abs() <= tolerance * expected_mean.abs().max(1.0), "mean {} vs {}", mean, expected_mean);
+        assert!(
+            (variance as f64 - expected_variance).abs() <= tolerance * expected_variance.max(1.0),
+            "variance {} vs {}",
+            variance,
+            expected_variance
+        );
+    }
+
+    #[test]
+    fn welford_matches_two_pass() {
+        let values: [f32; 8] = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(mean_and_variance(&values), (5.0, 4.0));
+        assert_close(&[0.5, 1.5, 3.0, 10.0, 0.25], 1e-6);
+        assert!(mean_and_variance(&[]).0.is_nan());
+    }
+
+    #[test]
+    fn welford_is_stable_at_high_magnitude() {
+        let mut values: [f32; 1000] = [0.0; 1000];
+        for (index, value) in values.iter_mut().enumerate() {
+            *value = 1.0e6 + (index % 7) as f32;
+        }
+        assert_close(&values, 1e-3);
+    }
+
+    #[test]
+    fn scan_runs_without_allocation() {
+        // A sensor buffer and a fixed-size result table, as on a microcontroller without an
+        // allocator.
+        let readings: [Value; 12] = [10, 11, 12, 13, 14, 40, 41, 42, 43, 44, 45, 90];
+        let options = ScanOptions {
+            thresholds: thresholds(&readings, 1.5, None, None, false, |_, _| false),
+            min_cluster_size: 3,
+            inclusive_cluster_threshold: false,
+            inclusive_gap_threshold: false,
+            report_dead_zones: false,
+            edge_gaps: false,
+        };
+        let mut found: [Option<Segment>; 8] = [None; 8];
+        let mut count: usize = 0;
+        scan(&readings, &options, |_, _| false, |segment: Segment| {
+            found[count] = Some(segment);
+            count += 1;
+        });
+        assert_eq!(
+            found[..count],
+            [
+                Some(Segment::Cluster { first: 0, last: 4 }),
+                Some(Segment::Gap { position: 4 }),
+                Some(Segment::Cluster { first: 5, last: 10 }),
+                Some(Segment::Gap { position: 10 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn running_statistics_match_the_slice() {
+        let values: [f32; 5] = [0.5, 1.5, 3.0, 10.0, 0.25];
+        let mut statistics: RunningStatistics = RunningStatistics::new();
+        values.iter().for_each(|&value: &f32| statistics.push(value));
+        assert_eq!(statistics.count(), 5);
+        assert_eq!(statistics.mean_and_variance(), mean_and_variance(&values));
+        assert!(RunningStatistics::new().mean_and_variance().1.is_nan());
+    }
+}
@@ -0,0 +1,29 @@
+//! Checks that the crate builds for a bare-metal target without the standard library.
+
+use std::path::Path;
+use std::process::Command;
+
+/// A Cortex-M4F target, which has no `std` to link against.
+const TARGET: &str = "thumbv7em-none-eabihf";
+
+#[test]
+fn builds_for_a_target_without_std() {
+    let sysroot: String = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .expect("rustc runs");
+    if !Path::new(&sysroot).join("lib/rustlib").join(TARGET).exists() {
+        eprintln!("Skipping the no_std build check: the {} target is not installed", TARGET);
+        return;
+    }
+    let manifest_dir: &str = env!("CARGO_MANIFEST_DIR");
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--target", TARGET, "--manifest-path"])
+        .arg(Path::new(manifest_dir).join("Cargo.toml"))
+        // A separate target directory avoids waiting on the lock of the running test build.
+        .env("CARGO_TARGET_DIR", Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_std"))
+        .status()
+        .expect("cargo runs");
+    assert!(status.success(), "lyagushka-core does not build for {}", TARGET);
+}
@@ -1,8 +1,9 @@
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, stdin};
+use std::io::{self, BufRead, BufReader, Write, stdin};
 use std::env;
 use std::process;
-use lyagushka::Lyagushka;
+use lyagushka::{generate_clusters, parse_lines, Anomaly, Error, Lyagushka, ParseOptions, ParsedInput, SearchConfig, Value};
+use serde::Serialize;
 
 /// The entry point for the command-line tool that reads a dataset of integers from either a file or stdin,
 /// performs cluster and gap analysis using specified parameters, and prints the results as a JSON string.
@@ -28,13 +29,118 @@ use lyagushka::Lyagushka;
 /// - `factor`: A floating-point value used to adjust the sensitivity of cluster and gap detection.
 /// - `min_cluster_size`: The minimum number of contiguous points required to be considered a cluster.
 ///
+/// The `factor` and `min_cluster_size` arguments can be left out if the `LYAGUSHKA_FACTOR` and
+/// `LYAGUSHKA_MIN_CLUSTER_SIZE` environment variables are set; arguments take precedence.
+///
+/// # Options
+/// - `--mode <values|deltas>`: Analyze the input values (default) or the differences between successive
+///   input values.
+/// - `--sensitivity <s>`: Replace the `factor` argument by a sensitivity between `0` and `1`, where
+///   higher values report more anomalies.
+/// - `--strict`: Fail on input lines that are not numbers or out of range, instead of skipping them.
+/// - `--parse-grouped`: Accept digit grouping such as `1,000` or `1_000` in the input.
+/// - `--jsonl-input`: Read one JSON value per line: a number, or an object with the number in a `value` field.
+/// - `--jsonl-field <name>`: Read the numbers of JSON objects from the field `name`; implies `--jsonl-input`.
+/// - `--comment-prefix <prefix>`: Skip lines starting with `prefix` (default `#`), even with `--strict`;
+///   an empty prefix turns comments off.
+/// - `--on-nonfinite <error|drop>`: Fail on input lines holding NaN or an infinity (default), or skip them.
+/// - `--weighted-input`: Read two columns per line, a value and its frequency, and weight densities by the frequencies.
+/// - `--assume-sorted`: Skip sorting the input, which must already be in ascending order.
+/// - `--require-sorted`: Fail instead of sorting if the input is not in ascending order.
+/// - `--log-gap-spans`: Score gaps by standardizing `ln(span_length)` against the other gaps.
+/// - `--log-densities`: Score clusters by standardizing `ln(density)` against the other clusters.
+/// - `--shrinkage`: Pull the density of every cluster towards the center by `1 / sqrt(num_elements)` of its
+///   deviation before scoring it.
+/// - `--adaptive`: Exclude the gaps found by a first pass from the mean distance and scan again.
+/// - `--sorted-output-by-input-order`: List cluster elements in input order, with their input `indices`.
+/// - `--density-smoothing <eps>`: Add `eps` to the span when calculating cluster densities.
+/// - `--density-weight <w>`: Score clusters by `w` times their density z-score plus `1 - w` times
+///   their span z-score.
+/// - `--expected-spacing <s>`: Report each cluster's relative deviation from the nominal point spacing `s`.
+/// - `--weak-clusters <z>`: Flag clusters with a density z-score below `z` as `weak`, or with a
+///   density percentile below `z` under `--score-method rank`.
+/// - `--stability <band>`: Score every anomaly by the fraction of factors within `1 ± band` times the
+///   chosen ones at which it is detected.
+/// - `--max-passes <n>`: Run at most `n` scans for `--stability`.
+/// - `--timeout <seconds>`: Start no further `--stability` scan once the analysis has run this long.
+/// - `--range <lo>:<hi>`: Analyze only the values within `[lo, hi]`.
+/// - `--epsilon <e>`: Treat values at most `e` above the first value of their run as the same point.
+/// - `--mask <lo>:<hi>`: Never report gaps reaching into `[lo, hi]`, a known sparse region; repeatable.
+/// - `--expect-dense <lo>:<hi>`: Leave out clusters within `[lo, hi]`, a known dense region; repeatable.
+/// - `--sample <fraction>`: Analyze a uniform random sample of about `fraction` of the points.
+/// - `--seed <n>`: Seed for `--sample` (default `0`).
+/// - `--periodicity`: Report a dominant period of the consecutive distances as `detected_period` in the
+///   summary.
+/// - `--edge-gaps`: Report sparse stretches at the ends of the range as gaps flagged `edge`.
+/// - `--gap-sign <positive|negative>`: Whether wider gaps get larger positive or (by default) larger
+///   negative z-scores.
+/// - `--unified-score`: Standardize gaps within their own type so clusters and gaps share one scale.
+/// - `--score-method <classic|robust|modified|rank>`: The formula anomalies are scored by.
+/// - `--enter-factor <f>` / `--exit-factor <f>`: Separate factors for starting and continuing a cluster.
+/// - `--exclusive-cluster-threshold`: Only join points strictly closer than the cluster threshold.
+/// - `--inclusive-gap-threshold`: Also record gaps whose span equals the gap threshold.
+/// - `--dead-zones`: Report distances between the two thresholds as gaps flagged `dead_zone`.
+/// - `--output <json|ndjson|report|features|boundaries|bundle|msgpack|arrow>`: Print a plain JSON array (default),
+///   one JSON anomaly per line, an object with a dataset summary next to the anomalies, a GeoJSON-like FeatureCollection, the sorted
+///   start and end positions, an object with the sorted dataset, the parameters and the anomalies,
+///   the plain array as binary MessagePack (requires the `msgpack` feature), or the anomalies as an
+///   Arrow IPC stream (requires the `arrow` feature).
+/// - `--out <path>`: Write the output to the file at `path` instead of stdout.
+/// - `--parallel <n>`: Analyze every file given before the parameters on `n` threads and print the
+///   anomalies of all of them as NDJSON, each with the file it came from as `source`. Cannot be
+///   combined with other outputs.
+/// - `--boundaries`: Shorthand for `--output boundaries`.
+/// - `--clamp-z <max>`: Cap the absolute z-scores in the output at `max`, keeping their sign.
+/// - `--correct-multiple <bonferroni|bh>`: Add the p-value of every z-score and flag the anomalies that are
+///   `significant` after a Bonferroni or Benjamini-Hochberg correction.
+/// - `--alpha <level>`: Significance level of `--correct-multiple` (default `0.05`).
+/// - `--rank`: Rank every scored anomaly among those of its type, from `1` for the most extreme.
+/// - `--fail-on-anomaly <z>`: Exit with status `2` after printing the output if any anomaly has an
+///   absolute z-score above `z`. Cannot be combined with `--clamp-z`.
+/// - `--max-elements <n>`: Leave out the `elements` of clusters with more than `n` points.
+/// - `--max-clusters <n>` / `--max-gaps <n>`: Print at most `n` clusters or gaps, the most extreme ones,
+///   and mark the summary `truncated` if any were left out. The analysis holds no more anomalies
+///   than that either.
+/// - `--explain`: Attach a human-readable explanation of its z-score to every anomaly.
+/// - `--explain-params`: Print the resolved configuration and the derived thresholds to stderr.
+/// - `--compact`: Print JSON without indentation.
+/// - `--canonical`: Print JSON with sorted keys and floats at a fixed precision, for golden files.
+/// - `--pretty-indent`: Indent JSON by the given number of spaces per level, or by a tab with `tab`.
+/// - `--round-centroid <nearest|floor|ceil>`: Print centroids as integers, rounded in the given direction.
+/// - `--bin-edges <file|list>`: Instead of analyzing, print the number of points between consecutive
+///   edges, given as a comma-separated list or a file, with dense and sparse bins marked.
+/// - `--profile <resolution>`: Instead of analyzing, print the local point density at `resolution` evenly
+///   spaced positions across the range of the values.
+/// - `--two-scale <coarse_factor>`: Print the clusters grouped into coarse clusters, found among the
+///   cluster centroids with the looser `coarse_factor`, next to the clusters belonging to none.
+/// - `--threshold-report <buckets>`: Instead of analyzing, print a histogram of the distances between
+///   consecutive points with the cluster and gap thresholds.
+/// - `--emit-distances`: Instead of analyzing, print the distances between consecutive points of the
+///   sorted dataset, one per line.
+/// - `--units <label>`: Record the unit of the input values in the summary; implies `--output report`.
+/// - `--gap-interiors`: Add the first and last value strictly between the bounding points of every gap.
+/// - `--gap-centers`: Add the midpoint of every gap between the edges of its neighbouring clusters.
+/// - `--core-points`: Count the DBSCAN-style core and border points of every cluster.
+/// - `--time-unit <ns|us|ms|s|min|h|d>`: Treat the values as timestamps in this unit and add the span of
+///   every anomaly as a `duration` such as `1h 30m`.
+///
+/// To generate a synthetic clustered dataset instead:
+/// ```
+/// cargo run -- generate --clusters 5 --points-per-cluster 20 --spread 10 --gap 1000 --seed 42
+/// ```
+///
 /// # Exit Codes
 /// - `0`: Success.
 /// - `1`: Incorrect usage or failure to parse the input data.
+/// - `2`: With `--fail-on-anomaly`, an anomaly exceeded the threshold.
 ///
 /// # Errors
 /// This tool will exit with an error if the required arguments are not provided, if the specified file cannot be opened,
-/// or if the input data cannot be parsed into integers.
+/// or, with `--strict`, if any input line cannot be parsed into an integer. Without it, such lines are skipped, with
+/// a warning on stderr for integers out of range. Lines holding NaN or an infinity are an error unless
+/// `--on-nonfinite drop` is given.
+/// It also exits with an error if the distance between the smallest and the largest value overflows the value type,
+/// as it can for datasets of both large negative and large positive values.
 ///
 /// # Note
 /// This function does not return a value but directly exits the process in case of failure.
@@ -42,29 +148,636 @@ use lyagushka::Lyagushka;
 fn main() -> io::Result<()> {
     let args: Vec<String> = env::args().collect();
 
-    // Input handling
-    let dataset: Vec<i32> = if atty::is(atty::Stream::Stdin) {
-        if args.len() != 4 {
-            eprintln!("Usage: {} <filename> <factor> <min_cluster_size>", args[0]);
-            process::exit(1);
+    if args.get(1).map(String::as_str) == Some("generate") {
+        generate(&args[2..]);
+        return Ok(());
+    }
+
+    // Option handling; `factor` and `min_cluster_size` are filled in from the positional arguments below.
+    let mut config: SearchConfig = SearchConfig::new(0.0, 0);
+    let mut positional: Vec<&String> = Vec::new();
+    let mut output: Option<OutputFormat> = None;
+    let mut threshold_report: Option<usize> = None;
+    let mut bin_edges: Option<Vec<Value>> = None;
+    let mut profile: Option<usize> = None;
+    let mut two_scale: Option<f32> = None;
+    let mut sensitivity: Option<f32> = None;
+    let mut require_sorted: bool = false;
+    let mut emit_distances: bool = false;
+    let mut mode: Mode = Mode::Values;
+    let mut parsing: ParseOptions = ParseOptions::default();
+    let mut out_path: Option<String> = None;
+    let mut fail_on_anomaly: Option<f32> = None;
+    let mut explain_params: bool = false;
+    let mut parallel: Option<usize> = None;
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--mode" => mode = parse_flag_value(arg, iter.next()),
+            "--sensitivity" => sensitivity = Some(parse_flag_value(arg, iter.next())),
+            "--strict" => parsing.strict = true,
+            "--parse-grouped" => parsing.grouped = true,
+            "--jsonl-input" => {
+                parsing.jsonl_field.get_or_insert_with(|| "value".to_string());
+            }
+            "--jsonl-field" => parsing.jsonl_field = Some(parse_flag_value(arg, iter.next())),
+            "--weighted-input" => parsing.weighted = true,
+            "--comment-prefix" => {
+                // An empty prefix turns comments off, as every line would start with it.
+                let prefix: String = parse_flag_value(arg, iter.next());
+                parsing.comment_prefix = Some(prefix).filter(|prefix: &String| !prefix.is_empty());
+            }
+            "--on-nonfinite" => parsing.on_nonfinite = parse_flag_value(arg, iter.next()),
+            "--require-sorted" => require_sorted = true,
+            "--assume-sorted" => config.assume_sorted = true,
+            "--log-gap-spans" => config.log_gap_spans = true,
+            "--log-densities" => config.log_densities = true,
+            "--shrinkage" => config.shrinkage = true,
+            "--adaptive" => config.adaptive = true,
+            "--enter-factor" => config.enter_factor = Some(parse_flag_value(arg, iter.next())),
+            "--exit-factor" => config.exit_factor = Some(parse_flag_value(arg, iter.next())),
+            "--density-weight" => config.density_weight = Some(parse_flag_value(arg, iter.next())),
+            "--expected-spacing" => config.expected_spacing = Some(parse_flag_value(arg, iter.next())),
+            "--weak-clusters" => config.weak_cluster_threshold = Some(parse_flag_value(arg, iter.next())),
+            "--stability" => config.stability_band = Some(parse_flag_value(arg, iter.next())),
+            "--max-passes" => config.max_passes = Some(parse_flag_value(arg, iter.next())),
+            "--timeout" => config.timeout = Some(parse_flag_value(arg, iter.next())),
+            "--range" => config.range = Some(parse_range(arg, iter.next())),
+            "--epsilon" => config.epsilon = Some(parse_flag_value(arg, iter.next())),
+            "--mask" => config.masks.push(parse_range(arg, iter.next())),
+            "--expect-dense" => config.expected_dense.push(parse_range(arg, iter.next())),
+            "--sample" => config.sample = Some(parse_flag_value(arg, iter.next())),
+            "--seed" => config.seed = parse_flag_value(arg, iter.next()),
+            "--periodicity" => config.periodicity = true,
+            "--edge-gaps" => config.edge_gaps = true,
+            "--unified-score" => config.unified_score = true,
+            "--score-method" => config.score_method = parse_flag_value(arg, iter.next()),
+            "--gap-sign" => config.positive_gap_scores = parse_flag_value::<GapSign>(arg, iter.next()) == GapSign::Positive,
+            "--density-smoothing" => config.density_smoothing = Some(parse_flag_value(arg, iter.next())),
+            "--exclusive-cluster-threshold" => config.inclusive_cluster_threshold = false,
+            "--inclusive-gap-threshold" => config.inclusive_gap_threshold = true,
+            "--dead-zones" => config.report_dead_zones = true,
+            "--sorted-output-by-input-order" => config.input_order = true,
+            "--out" => out_path = Some(parse_flag_value(arg, iter.next())),
+            "--output" => output = Some(parse_flag_value(arg, iter.next())),
+            "--boundaries" => output = Some(OutputFormat::Boundaries),
+            "--clamp-z" => config.clamp_z = Some(parse_flag_value(arg, iter.next())),
+            "--correct-multiple" => config.correction = Some(parse_flag_value(arg, iter.next())),
+            "--alpha" => config.alpha = parse_flag_value(arg, iter.next()),
+            "--fail-on-anomaly" => fail_on_anomaly = Some(parse_flag_value(arg, iter.next())),
+            "--max-elements" => config.max_elements = Some(parse_flag_value(arg, iter.next())),
+            "--max-clusters" => config.max_clusters = Some(parse_flag_value(arg, iter.next())),
+            "--max-gaps" => config.max_gaps = Some(parse_flag_value(arg, iter.next())),
+            "--explain" => config.explain = true,
+            "--explain-params" => explain_params = true,
+            "--parallel" => parallel = Some(parse_flag_value(arg, iter.next())),
+            "--compact" => config.compact = true,
+            "--canonical" => config.canonical = true,
+            "--pretty-indent" => config.indent = Some(parse_indent(arg, iter.next())),
+            "--round-centroid" => config.round_centroid = Some(parse_flag_value(arg, iter.next())),
+            "--emit-distances" => emit_distances = true,
+            "--threshold-report" => threshold_report = Some(parse_flag_value(arg, iter.next())),
+            "--bin-edges" => bin_edges = Some(parse_edges(arg, iter.next())),
+            "--profile" => profile = Some(parse_flag_value(arg, iter.next())),
+            "--two-scale" => two_scale = Some(parse_flag_value(arg, iter.next())),
+            "--units" => config.units = Some(parse_flag_value(arg, iter.next())),
+            "--time-unit" => config.time_unit = Some(parse_flag_value(arg, iter.next())),
+            "--gap-interiors" => config.gap_interiors = true,
+            "--gap-centers" => config.gap_centers = true,
+            "--core-points" => config.core_points = true,
+            "--rank" => config.rank = true,
+            flag if flag.starts_with("--") => {
+                eprintln!("Unknown option: {}", flag);
+                process::exit(1);
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    // Parameter handling. With `--sensitivity` the factor is left out of the positional arguments.
+    // Parameters missing from the command line are taken from the `LYAGUSHKA_FACTOR` and
+    // `LYAGUSHKA_MIN_CLUSTER_SIZE` environment variables; a single positional parameter is the factor
+    // if the minimum cluster size is set in the environment, and the minimum cluster size otherwise.
+    let from_file: bool = atty::is(atty::Stream::Stdin);
+    let parameters_usage: &str = if sensitivity.is_some() { "<min_cluster_size>" } else { "<factor> <min_cluster_size>" };
+    let usage = || -> ! {
+        let filename_usage: &str = if from_file { "<filename> " } else { "" };
+        eprintln!("Usage: {} [options] {}{}", args[0], filename_usage, parameters_usage);
+        eprintln!("Parameters can also be set with LYAGUSHKA_FACTOR and LYAGUSHKA_MIN_CLUSTER_SIZE.");
+        process::exit(1);
+    };
+    let (filename, parameters): (Option<&String>, &[&String]) = if from_file && parallel.is_none() {
+        match positional.split_first() {
+            Some((filename, parameters)) => (Some(*filename), parameters),
+            None => usage(),
         }
-        let filename = &args[1];
-        let file = File::open(filename)?;
-        BufReader::new(file).lines().filter_map(Result::ok)
-            .filter_map(|line| line.trim().parse::<i32>().ok()) // Directly parse to i32
-            .collect()
     } else {
-        stdin().lock().lines().filter_map(Result::ok)
-            .filter_map(|line| line.trim().parse::<i32>().ok()) // Directly parse to i32
-            .collect()
+        // Piped input allows leading extra arguments, the files of `--parallel`; the parameters
+        // are the trailing ones.
+        let num_parameters: usize = if sensitivity.is_some() { 1 } else { 2 };
+        if parallel.is_none() && positional.len() > num_parameters {
+            usage();
+        }
+        (None, &positional[positional.len().saturating_sub(num_parameters)..])
+    };
+    let env_factor: Option<String> = env::var("LYAGUSHKA_FACTOR").ok();
+    let env_min_cluster_size: Option<String> = env::var("LYAGUSHKA_MIN_CLUSTER_SIZE").ok();
+    let (factor, min_cluster_size): (Option<String>, Option<String>) = match (sensitivity.is_some(), parameters) {
+        (true, [min_cluster_size]) => (None, Some(min_cluster_size.to_string())),
+        (true, []) => (None, env_min_cluster_size),
+        (false, [factor, min_cluster_size]) => (Some(factor.to_string()), Some(min_cluster_size.to_string())),
+        (false, [factor]) if env_min_cluster_size.is_some() => (Some(factor.to_string()), env_min_cluster_size),
+        (false, [min_cluster_size]) => (env_factor, Some(min_cluster_size.to_string())),
+        (false, []) => (env_factor, env_min_cluster_size),
+        _ => usage(),
+    };
+    let min_cluster_size: String = min_cluster_size.unwrap_or_else(|| usage());
+    config.factor = match (sensitivity, factor) {
+        (Some(sensitivity), _) => SearchConfig::with_sensitivity(sensitivity, 0).factor,
+        (None, Some(factor)) => factor.parse().expect("Factor must be a float"),
+        (None, None) => usage(),
     };
+    config.min_cluster_size = min_cluster_size.parse().expect("Min cluster size must be an integer");
+
+    if config.clamp_z.is_some_and(|max: f32| max.is_nan() || max < 0.0) {
+        eprintln!("Invalid value for --clamp-z: the cap must not be negative");
+        process::exit(1);
+    }
+    if config.clamp_z.is_some() && fail_on_anomaly.is_some() {
+        eprintln!("--fail-on-anomaly cannot be combined with --clamp-z, which would hide the scores it gates on");
+        process::exit(1);
+    }
+    if config.epsilon.is_some_and(|epsilon: Value| epsilon < 0) {
+        eprintln!("Invalid value for --epsilon: the tolerance must not be negative");
+        process::exit(1);
+    }
+    // The options the library restricts to a range are named like their flags.
+    if let Err(err) = config.validate() {
+        match err {
+            Error::InvalidOption { name, requirement } => eprintln!("Invalid value for --{}: {}", name, requirement),
+            err => eprintln!("{}", err),
+        }
+        process::exit(1);
+    }
+    if config.timeout.is_some_and(|seconds: f32| seconds.is_nan() || seconds < 0.0) {
+        eprintln!("Invalid value for --timeout: the time must not be negative");
+        process::exit(1);
+    }
+    // Grouping removes the separator between the value and frequency columns.
+    if parsing.weighted && (parsing.jsonl_field.is_some() || parsing.grouped || config.epsilon.is_some() || matches!(mode, Mode::Deltas)) {
+        eprintln!("--weighted-input cannot be combined with JSON input, --parse-grouped, --epsilon or --mode deltas");
+        process::exit(1);
+    }
+    // Parallel analysis has a single output, the NDJSON anomalies of all files.
+    if parallel.is_some() {
+        let other_outputs = [
+            ("--output", output.as_ref().is_some_and(|format: &OutputFormat| !matches!(format, OutputFormat::Ndjson))),
+            ("--emit-distances", emit_distances),
+            ("--threshold-report", threshold_report.is_some()),
+            ("--bin-edges", bin_edges.is_some()),
+            ("--profile", profile.is_some()),
+            ("--two-scale", two_scale.is_some()),
+            ("--explain-params", explain_params),
+            ("--units", config.units.is_some()),
+        ];
+        if let Some((flag, _)) = other_outputs.iter().find(|&&(_, set): &&(&str, bool)| set) {
+            eprintln!("--parallel cannot be combined with {}, as it prints the anomalies as NDJSON", flag);
+            process::exit(1);
+        }
+    }
 
-    let factor: f32 = args[args.len() - 2].parse().expect("Factor must be a float");
-    let min_cluster_size: usize = args[args.len() - 1].parse().expect("Min cluster size must be an integer");
+    // Output goes to the file given with `--out`, or to stdout.
+    let mut out: io::BufWriter<Box<dyn Write>> = io::BufWriter::new(match &out_path {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    });
+    if let Some(threads) = parallel {
+        let files: &[&String] = &positional[..positional.len() - parameters.len()];
+        let anomalies: Vec<Anomaly> = analyze_files(files, threads, &mode, &parsing, &config, require_sorted, &mut out)?;
+        out.flush()?;
+        fail_if_exceeding(&anomalies, fail_on_anomaly);
+        return Ok(());
+    }
+
+    // Input handling
+    let dataset: Vec<(Value, usize)> = match filename {
+        Some(filename) => {
+            let file = File::open(filename)?;
+            read_lines(BufReader::new(file).lines().map_while(Result::ok), &parsing)
+        }
+        None => read_lines(stdin().lock().lines().map_while(Result::ok), &parsing),
+    };
 
     // Analysis and output
-    let mut zhaba = Lyagushka::new(dataset);
-    println!("{}", zhaba.search(factor, min_cluster_size));
+    let mut zhaba = load(dataset, &mode, &parsing);
+    if require_sorted {
+        if let Err(err) = zhaba.check_sorted() {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+    if emit_distances {
+        for distance in zhaba.distances() {
+            writeln!(out, "{}", distance)?;
+        }
+        return out.flush();
+    }
+    if let Some(buckets) = threshold_report {
+        writeln!(out, "{}", zhaba.threshold_report(&config, buckets))?;
+        return out.flush();
+    }
+    if let Some(edges) = bin_edges {
+        writeln!(out, "{}", zhaba.bin_report(&config, edges))?;
+        return out.flush();
+    }
+    if let Some(resolution) = profile {
+        writeln!(out, "{}", zhaba.profile_report(&config, resolution))?;
+        return out.flush();
+    }
+    if let Some(coarse_factor) = two_scale {
+        writeln!(out, "{}", zhaba.two_scale_report(&config, coarse_factor))?;
+        return out.flush();
+    }
+    // The plain array has nowhere to carry the units label, so `--units` implies the report format.
+    let output: OutputFormat = output.unwrap_or(if config.units.is_some() { OutputFormat::Report } else { OutputFormat::Json });
+    zhaba.analyze(&config);
+    let summary = zhaba.summary();
+    if explain_params {
+        // The thresholds are those the analysis derived, after `--range`, `--sample` and `--mask`.
+        let params = EffectiveParameters {
+            parameters: &config,
+            mean_distance: summary.mean_distance,
+            cluster_threshold: summary.cluster_threshold,
+            cluster_exit_threshold: summary.cluster_exit_threshold,
+            gap_threshold: summary.gap_threshold,
+        };
+        eprintln!("{}", serde_json::to_string_pretty(&params).expect("parameters serialize to JSON"));
+    }
+    if summary.num_clusters == 0 && summary.num_discarded_clusters > 0 {
+        eprintln!(
+            "Warning: all {} candidate clusters had fewer than {} points and were discarded",
+            summary.num_discarded_clusters, config.min_cluster_size
+        );
+    }
+    match output {
+        OutputFormat::Json => {
+            zhaba.write_json(&mut out).unwrap_or_else(|err| {
+                eprintln!("Failed to serialize data: {}", err);
+                process::exit(1);
+            });
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => zhaba.write_ndjson(&mut out).unwrap_or_else(|err| {
+            eprintln!("Failed to serialize data: {}", err);
+            process::exit(1);
+        }),
+        OutputFormat::Report => writeln!(out, "{}", zhaba.to_report())?,
+        OutputFormat::Features => writeln!(out, "{}", zhaba.to_features())?,
+        OutputFormat::Boundaries => writeln!(out, "{}", zhaba.to_boundaries())?,
+        OutputFormat::Bundle => writeln!(out, "{}", zhaba.to_bundle())?,
+        #[cfg(feature = "msgpack")]
+        OutputFormat::Msgpack => {
+            let bytes: Vec<u8> = zhaba.to_msgpack().unwrap_or_else(|err| {
+                eprintln!("Failed to serialize data: {}", err);
+                process::exit(1);
+            });
+            out.write_all(&bytes)?;
+        }
+        #[cfg(feature = "arrow")]
+        OutputFormat::Arrow => {
+            let bytes: Vec<u8> = zhaba.to_arrow().unwrap_or_else(|err| {
+                eprintln!("Failed to serialize data: {}", err);
+                process::exit(1);
+            });
+            out.write_all(&bytes)?;
+        }
+    }
+    out.flush()?;
 
+    fail_if_exceeding(zhaba.anomalies(), fail_on_anomaly);
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Exits with status `2` if any of the printed `anomalies` has an absolute z-score above the
+/// `--fail-on-anomaly` threshold. As a gate, the output is complete before the status reports
+/// the verdict.
+fn fail_if_exceeding(anomalies: &[Anomaly], threshold: Option<f32>) {
+    if let Some(threshold) = threshold {
+        let exceeding: usize = anomalies.iter()
+            .filter(|info: &&Anomaly| info.z_score.is_some_and(|z: f32| z.abs() > threshold))
+            .count();
+        if exceeding > 0 {
+            eprintln!("{} of {} anomalies have an absolute z-score above {}", exceeding, anomalies.len(), threshold);
+            process::exit(2);
+        }
+    }
+}
+
+/// Creates the instance to analyze from the parsed input, exiting with an error message if its
+/// range, or that of its differences, is too wide.
+fn load(dataset: Vec<(Value, usize)>, mode: &Mode, parsing: &ParseOptions) -> Lyagushka {
+    let values = |dataset: Vec<(Value, usize)>| -> Vec<Value> { dataset.into_iter().map(|(value, _)| value).collect() };
+    let zhaba: Result<Lyagushka, Error> = match mode {
+        Mode::Values if parsing.weighted => Lyagushka::from_frequencies(dataset),
+        Mode::Values => Lyagushka::new(values(dataset)),
+        Mode::Deltas => Lyagushka::from_deltas(values(dataset)),
+    };
+    match zhaba {
+        Ok(zhaba) => zhaba,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// An anomaly of `--parallel` output, tagged with the file it was found in.
+#[derive(Serialize)]
+struct SourcedAnomaly<'a> {
+    source: &'a str,
+    #[serde(flatten)]
+    anomaly: &'a Anomaly,
+}
+
+/// Analyzes `files` on `threads` worker threads and writes the anomalies of all of them to `out`
+/// as NDJSON, tagged with their `source`. The files are written in the order of their sorted
+/// names, whichever finishes first, and their anomalies are returned in the same order.
+///
+/// With `require_sorted`, a file that is not in ascending order is an error.
+fn analyze_files(
+    files: &[&String],
+    threads: usize,
+    mode: &Mode,
+    parsing: &ParseOptions,
+    config: &SearchConfig,
+    require_sorted: bool,
+    out: &mut impl Write,
+) -> io::Result<Vec<Anomaly>> {
+    let mut files: Vec<&String> = files.to_vec();
+    files.sort();
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    // Every worker takes the next file in line until none are left, and returns the anomalies
+    // of its files with their positions in the sorted list.
+    let analyze = || -> Vec<(usize, Vec<Anomaly>)> {
+        let mut results: Vec<(usize, Vec<Anomaly>)> = Vec::new();
+        loop {
+            let position: usize = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let Some(file) = files.get(position) else {
+                return results;
+            };
+            let reader = File::open(file).unwrap_or_else(|err| {
+                eprintln!("Failed to open {}: {}", file, err);
+                process::exit(1);
+            });
+            let mut zhaba: Lyagushka = load(read_lines(BufReader::new(reader).lines().map_while(Result::ok), parsing), mode, parsing);
+            if let (true, Err(err)) = (require_sorted, zhaba.check_sorted()) {
+                eprintln!("{}: {}", file, err);
+                process::exit(1);
+            }
+            zhaba.analyze(config);
+            results.push((position, zhaba.anomalies().to_vec()));
+        }
+    };
+    let mut results: Vec<(usize, Vec<Anomaly>)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.max(1)).map(|_| scope.spawn(analyze)).collect();
+        workers.into_iter().flat_map(|worker| worker.join().expect("worker thread panicked")).collect()
+    });
+    results.sort_by_key(|&(position, _): &(usize, Vec<Anomaly>)| position);
+
+    for (position, anomalies) in &results {
+        for anomaly in anomalies {
+            let line = SourcedAnomaly { source: files[*position].as_str(), anomaly };
+            serde_json::to_writer(&mut *out, &line).map_err(io::Error::from)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(results.into_iter().flat_map(|(_, anomalies): (usize, Vec<Anomaly>)| anomalies).collect())
+}
+
+/// What `--explain-params` prints: the configuration and the thresholds derived from it.
+#[derive(Serialize)]
+struct EffectiveParameters<'a> {
+    parameters: &'a SearchConfig,
+    mean_distance: f32,
+    cluster_threshold: f32,
+    cluster_exit_threshold: f32,
+    gap_threshold: f32,
+}
+
+/// The serialization formats selectable with `--output`.
+enum OutputFormat {
+    Json,
+    Ndjson,
+    Report,
+    Features,
+    Boundaries,
+    Bundle,
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    #[cfg(feature = "arrow")]
+    Arrow,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "report" => Ok(OutputFormat::Report),
+            "features" => Ok(OutputFormat::Features),
+            "boundaries" => Ok(OutputFormat::Boundaries),
+            "bundle" => Ok(OutputFormat::Bundle),
+            #[cfg(feature = "msgpack")]
+            "msgpack" => Ok(OutputFormat::Msgpack),
+            #[cfg(not(feature = "msgpack"))]
+            "msgpack" => Err("MessagePack output requires the `msgpack` feature".to_string()),
+            #[cfg(feature = "arrow")]
+            "arrow" => Ok(OutputFormat::Arrow),
+            #[cfg(not(feature = "arrow"))]
+            "arrow" => Err("Arrow output requires the `arrow` feature".to_string()),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+/// Reads the input with [`parse_lines`] and returns each value with its frequency, warning on
+/// stderr about the lines that were skipped rather than dropping them silently. Input that
+/// fails to parse ends the process with an error naming the lines.
+fn read_lines(lines: impl Iterator<Item = String>, options: &ParseOptions) -> Vec<(Value, usize)> {
+    let input: ParsedInput = parse_lines(lines, options).unwrap_or_else(|err| {
+        match err {
+            Error::NonFinite { .. } => eprintln!("{}; pass --on-nonfinite drop to skip them", err),
+            _ => eprintln!("{}", err),
+        }
+        process::exit(1);
+    });
+    if let Some(first) = input.out_of_range.first() {
+        eprintln!(
+            "Warning: dropped {} values out of range for the value type ({}..={}), the first on line {}",
+            input.out_of_range.len(), Value::MIN, Value::MAX, first
+        );
+    }
+    if let Some(first) = input.nonfinite.first() {
+        eprintln!("Warning: dropped {} non-finite values, the first on line {}", input.nonfinite.len(), first);
+    }
+    input.values
+}
+
+/// What to analyze, selectable with `--mode`.
+enum Mode {
+    /// The input values themselves.
+    Values,
+    /// The differences between successive input values.
+    Deltas,
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "values" => Ok(Mode::Values),
+            "deltas" => Ok(Mode::Deltas),
+            _ => Err(format!("Unknown mode: {}", s)),
+        }
+    }
+}
+
+/// The sign conventions for gap z-scores selectable with `--gap-sign`.
+#[derive(PartialEq)]
+enum GapSign {
+    Positive,
+    Negative,
+}
+
+impl std::str::FromStr for GapSign {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "positive" => Ok(GapSign::Positive),
+            "negative" => Ok(GapSign::Negative),
+            _ => Err(format!("Unknown gap sign: {}", s)),
+        }
+    }
+}
+
+/// Parses the value following a command-line flag, exiting with an error message if it is
+/// missing or malformed.
+fn parse_flag_value<T>(flag: &str, value: Option<&String>) -> T
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match value.map(|v| v.parse::<T>()) {
+        Some(Ok(parsed)) => parsed,
+        Some(Err(err)) => {
+            eprintln!("Invalid value for {}: {}", flag, err);
+            process::exit(1);
+        }
+        None => {
+            eprintln!("Missing value for {}", flag);
+            process::exit(1);
+        }
+    }
+}
+
+/// Parses the indentation of `--pretty-indent`: `tab`, or a number of spaces.
+fn parse_indent(flag: &str, value: Option<&String>) -> String {
+    match value.map(String::as_str) {
+        Some("tab") => "\t".to_string(),
+        _ => " ".repeat(parse_flag_value(flag, value)),
+    }
+}
+
+/// Parses the `lo:hi` value following a range flag, exiting with an error message if it is
+/// missing or malformed.
+fn parse_range(flag: &str, value: Option<&String>) -> (Value, Value) {
+    let bounds: Option<(&str, &str)> = value.and_then(|value: &String| value.split_once(':'));
+    match bounds.map(|(lo, hi): (&str, &str)| (lo.trim().parse::<Value>(), hi.trim().parse::<Value>())) {
+        Some((Ok(lo), Ok(hi))) if lo <= hi => (lo, hi),
+        _ => {
+            eprintln!("Invalid value for {}: expected <lo>:<hi> with lo <= hi", flag);
+            process::exit(1);
+        }
+    }
+}
+
+/// Parses the bin edges following `--bin-edges`: the path of a file listing them, or the edges
+/// themselves, separated by commas or whitespace. Exits with an error message if there are
+/// fewer than two or any is malformed.
+fn parse_edges(flag: &str, value: Option<&String>) -> Vec<Value> {
+    let Some(value) = value else {
+        eprintln!("Missing value for {}", flag);
+        process::exit(1);
+    };
+    let text: String = if std::path::Path::new(value).is_file() {
+        std::fs::read_to_string(value).unwrap_or_else(|err| {
+            eprintln!("Failed to read {}: {}", value, err);
+            process::exit(1);
+        })
+    } else {
+        value.clone()
+    };
+    let edges: Result<Vec<Value>, _> = text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|edge: &&str| !edge.is_empty())
+        .map(str::parse::<Value>)
+        .collect();
+    match edges {
+        Ok(edges) if edges.len() >= 2 => edges,
+        Ok(_) => {
+            eprintln!("Invalid value for {}: expected at least two edges", flag);
+            process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("Invalid value for {}: {}", flag, err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Handles the `generate` subcommand, printing a synthetic clustered dataset to stdout,
+/// one integer per line.
+///
+/// # Arguments
+/// - `--clusters`: Number of clusters to generate (default `5`).
+/// - `--points-per-cluster`: Number of points in each cluster (default `20`).
+/// - `--spread`: Maximum distance of a point from its cluster center (default `10`).
+/// - `--gap`: Distance between consecutive cluster centers (default `1000`).
+/// - `--seed`: Seed for the pseudo-random generator, for reproducible output (default `0`).
+fn generate(args: &[String]) {
+    let mut clusters: usize = 5;
+    let mut points_per_cluster: usize = 20;
+    let mut spread: i32 = 10;
+    let mut gap: i32 = 1000;
+    let mut seed: u64 = 0;
+
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        match flag.as_str() {
+            "--clusters" => clusters = parse_flag_value(flag, iter.next()),
+            "--points-per-cluster" => points_per_cluster = parse_flag_value(flag, iter.next()),
+            "--spread" => spread = parse_flag_value(flag, iter.next()),
+            "--gap" => gap = parse_flag_value(flag, iter.next()),
+            "--seed" => seed = parse_flag_value(flag, iter.next()),
+            _ => {
+                eprintln!("Usage: generate [--clusters <n>] [--points-per-cluster <n>] [--spread <n>] [--gap <n>] [--seed <n>]");
+                process::exit(1);
+            }
+        }
+    }
+
+    let data: Vec<i32> = generate_clusters(clusters, points_per_cluster, spread, gap, seed).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    for value in data {
+        if writeln!(out, "{}", value).is_err() {
+            break;
+        }
+    }
+}
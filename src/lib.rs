@@ -1,25 +1,167 @@
+use std::cmp::Reverse;
+use std::collections::binary_heap::{BinaryHeap, PeekMut};
+use std::fmt;
+use std::time::Instant;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use serde::Serialize;
+use pyo3::types::PyDict;
+use serde::{Deserialize, Serialize};
+use lyagushka_core::{mean_and_variance, RunningStatistics, ScanOptions, Segment, Thresholds};
 
-#[derive(Debug, Clone, Serialize)]
-struct Anomaly {
-    elements: Vec<i32>,
-    start: i32,
-    end: i32,
-    span_length: i32,
-    num_elements: usize,
-    centroid: f32,
-    z_score: Option<f32>,
+// Diagnostics go through the `log` facade with the `logging` feature, so that the host
+// application decides where they end up. Without it, the macros only type-check their arguments.
+#[cfg(feature = "logging")]
+use log::{debug, trace};
+#[cfg(not(feature = "logging"))]
+macro_rules! debug {
+    ($($arg:tt)*) => { if false { let _ = format_args!($($arg)*); } };
+}
+#[cfg(not(feature = "logging"))]
+macro_rules! trace {
+    ($($arg:tt)*) => { if false { let _ = format_args!($($arg)*); } };
+}
+
+pub use lyagushka_core::Value;
+
+/// Tag of the detection and scoring semantics, reported with every summary and bundle so that
+/// results can be traced to the behavior that produced them. Unlike the crate version it only
+/// changes, and must be bumped, when the same input and configuration can yield different
+/// anomalies or scores.
+///
+/// - `1`: The original semantics.
+/// - `2`: Means and standard deviations are computed with Welford's online algorithm, which can
+///   change the last digits of z-scores.
+/// - `3`: Centroids are computed in double precision, which changes them for values beyond the
+///   24-bit mantissa of `f32`.
+/// - `4`: With the robust and modified score methods, default-mode gap scores are centered on the
+///   median anomaly span instead of being a ratio to its spread.
+/// - `5`: Infinite densities of zero-span clusters are left out of the density statistics.
+pub const ALGORITHM_VERSION: u32 = 5;
+
+/// Number of factors sampled across the band of a stability analysis, see
+/// [`SearchConfig::stability_band`].
+const STABILITY_STEPS: usize = 5;
+
+/// Autocorrelation of the consecutive distances that a lag has to reach to count as the
+/// period of the dataset, see [`SearchConfig::periodicity`].
+const PERIODICITY_THRESHOLD: f32 = 0.5;
+
+/// A cluster (`num_elements > 0`) or gap (`num_elements == 0`) found in the dataset, see
+/// [`Anomaly::kind`].
+///
+/// The closed intervals `[start, end]` of the anomalies of an analysis may touch, as a gap
+/// starts at the last element of the cluster before it, but never overlap: no value lies
+/// strictly inside two of them, see [`Lyagushka::check_disjoint`].
+#[pyclass(get_all)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Anomaly {
+    pub elements: Vec<Value>,
+    pub start: Value,
+    pub end: Value,
+    pub span_length: Value,
+    pub num_elements: usize,
+    pub centroid: f32,
+    pub z_score: Option<f32>,
+    /// Percentile rank from `0` to `1` among the anomalies of the same type, reported instead of
+    /// `z_score` when scoring with [`ScoreMethod::Rank`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank_score: Option<f32>,
+    /// Position among the scored anomalies of the same type, clusters, gaps or dead zones, from
+    /// `1` for the score furthest from average. Scores equally far from average, on either side
+    /// of it, are ranked in ascending order of position. Set on request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<usize>,
+    /// Smallest distance between consecutive elements of a cluster.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub internal_gap_min: Option<Value>,
+    /// Largest distance between consecutive elements of a cluster.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub internal_gap_max: Option<Value>,
+    /// Mean distance between consecutive elements of a cluster.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub internal_gap_mean: Option<f32>,
+    /// How evenly a cluster's points are spread over its span, from `1` for equal distances
+    /// between all consecutive elements towards `0` for bunched-up ones: `1 / (1 + cv)` with
+    /// `cv` the coefficient of variation of the internal gaps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tightness: Option<f32>,
+    /// Relative deviation of `internal_gap_mean` from the configured expected spacing, e.g.
+    /// `0.5` for a 50% wider spacing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spacing_deviation: Option<f32>,
+    /// Number of elements of a cluster that are DBSCAN-style core points, having at least
+    /// `min_cluster_size` elements, themselves included, within the cluster threshold.
+    /// Set on request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub core_count: Option<usize>,
+    /// Number of elements of a cluster that are not core points, see `core_count`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub border_count: Option<usize>,
+    /// First value of the empty interior of a gap, `start + 1`: both `start` and `end` are data
+    /// points. Set on request for gaps with at least one value between their bounding points.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub empty_start: Option<Value>,
+    /// Last value of the empty interior of a gap, `end - 1`, see `empty_start`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub empty_end: Option<Value>,
+    /// Midpoint of a gap between the edges of the closest clusters on either side, in place of
+    /// bounding points that belong to no cluster. Set on request for gaps with a cluster on at
+    /// least one side.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gap_center: Option<f32>,
+    /// `span_length` as a human-readable duration such as `1h 30m`, if the values are timestamps
+    /// in the configured [`SearchConfig::time_unit`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+    /// Set for gaps that lie between the cluster and gap thresholds, reported only on request.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dead_zone: bool,
+    /// Set for sparse regions between a dataset bound and the first or last anomaly, reported only on request.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub edge: bool,
+    /// Set for clusters whose density z-score, or density percentile under
+    /// [`ScoreMethod::Rank`], falls below the configured weak-cluster threshold.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub weak: bool,
+    /// Set for clusters whose `elements` (and `indices`) were left out for exceeding the
+    /// configured maximum; `num_elements` still holds the full count.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub elements_truncated: bool,
+    /// Input positions of a cluster's elements, kept when the input order is restored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indices: Option<Vec<usize>>,
+    /// Human-readable rationale for the z-score, generated on request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+    /// Fraction of the factors in the stability band at which the anomaly was detected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stability: Option<f32>,
+    /// Two-sided p-value of the z-score under a standard normal distribution. Set for scored
+    /// anomalies when a multiple-comparison correction is requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p_value: Option<f32>,
+    /// Whether the anomaly is significant at the configured level after the correction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub significant: Option<bool>,
 }
 
 impl Anomaly {
 
-    pub fn new(cluster: &[i32]) -> Self {
+    pub fn new(cluster: &[Value]) -> Self {
         let num_elements: usize = cluster.len();
-        let start: i32 = *cluster.first().expect("Cluster has no start");
-        let end: i32 = *cluster.last().expect("Cluster has no end");
-        let span_length: i32 = end - start;
-        let centroid: f32 = start as f32 + span_length as f32 / 2.0;
+        let start: Value = *cluster.first().expect("Cluster has no start");
+        let end: Value = *cluster.last().expect("Cluster has no end");
+        let span_length: Value = end - start;
+        // Halved in f64 so that large or negative positions are rounded once, to the f32
+        // nearest to the true midpoint, rather than once for `start` and again for the sum.
+        let centroid: f32 = ((start as f64 + end as f64) / 2.0) as f32;
+        let internal_gaps = || cluster.windows(2).map(|w: &[Value]| w[1] - w[0]);
+        let internal_gap_mean: Option<f32> = (num_elements > 1).then(|| span_length as f32 / (num_elements - 1) as f32);
+        let tightness: Option<f32> = internal_gap_mean.map(|mean: f32| {
+            let internal_gaps: Vec<f32> = internal_gaps().map(|gap: Value| gap as f32).collect();
+            let (_, std_dev) = mean_and_std_dev(&internal_gaps);
+            if mean > 0.0 { 1.0 / (1.0 + std_dev / mean) } else { 1.0 }
+        });
 
         Anomaly {
             elements: cluster.to_vec(),
@@ -29,132 +171,4869 @@ impl Anomaly {
             num_elements,
             centroid,
             z_score: None,
+            rank_score: None,
+            rank: None,
+            internal_gap_min: internal_gaps().min(),
+            internal_gap_max: internal_gaps().max(),
+            internal_gap_mean,
+            tightness,
+            spacing_deviation: None,
+            core_count: None,
+            border_count: None,
+            empty_start: None,
+            empty_end: None,
+            gap_center: None,
+            duration: None,
+            dead_zone: false,
+            edge: false,
+            weak: false,
+            elements_truncated: false,
+            indices: None,
+            explanation: None,
+            stability: None,
+            p_value: None,
+            significant: None,
+        }
+    }
+
+    /// Compares two anomalies, with the float fields `centroid`, `z_score`, `rank_score`,
+    /// `internal_gap_mean`, `tightness`, `spacing_deviation`, `gap_center`, `stability` and `p_value` allowed to differ by up to `eps` and
+    /// all other fields required to be equal.
+    ///
+    /// Two missing or two NaN z-scores are considered equal.
+    pub fn approx_eq(&self, other: &Anomaly, eps: f32) -> bool {
+        let close = |a: f32, b: f32| (a.is_nan() && b.is_nan()) || (a - b).abs() <= eps;
+        let options_close = |a: Option<f32>, b: Option<f32>| match (a, b) {
+            (Some(a), Some(b)) => close(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+
+        self.elements == other.elements
+            && self.start == other.start
+            && self.end == other.end
+            && self.span_length == other.span_length
+            && self.num_elements == other.num_elements
+            && self.rank == other.rank
+            && self.internal_gap_min == other.internal_gap_min
+            && self.internal_gap_max == other.internal_gap_max
+            && self.dead_zone == other.dead_zone
+            && self.edge == other.edge
+            && self.weak == other.weak
+            && self.elements_truncated == other.elements_truncated
+            && self.indices == other.indices
+            && self.explanation == other.explanation
+            && self.core_count == other.core_count
+            && self.border_count == other.border_count
+            && self.empty_start == other.empty_start
+            && self.empty_end == other.empty_end
+            && self.duration == other.duration
+            && close(self.centroid, other.centroid)
+            && options_close(self.z_score, other.z_score)
+            && options_close(self.rank_score, other.rank_score)
+            && options_close(self.internal_gap_mean, other.internal_gap_mean)
+            && options_close(self.tightness, other.tightness)
+            && options_close(self.spacing_deviation, other.spacing_deviation)
+            && options_close(self.gap_center, other.gap_center)
+            && options_close(self.stability, other.stability)
+            && options_close(self.p_value, other.p_value)
+            && self.significant == other.significant
+    }
+
+    /// The key anomalies are ordered by: their `start`, then their `end`, with clusters before
+    /// gaps between the same two positions, so that ties such as a zero-span cluster and the gap
+    /// starting at it are ordered the same way on every run.
+    fn sort_key(&self) -> (Value, Value, bool) {
+        (self.start, self.end, self.kind() == AnomalyKind::Gap)
+    }
+
+    /// How far the score of the anomaly is from the `average` score, if it has a score.
+    fn extremity(&self, average: f32) -> Option<f32> {
+        self.z_score.or(self.rank_score).map(|score: f32| (score - average).abs()).filter(|extremity: &f32| !extremity.is_nan())
+    }
+
+    /// Whether the anomaly contributes to the statistics its z-score is measured against.
+    fn in_statistics(&self) -> bool {
+        !self.dead_zone && !self.edge
+    }
+
+    /// Whether `other`, found with a different factor, is a detection of this anomaly: an
+    /// overlapping cluster, or a gap between the same two points.
+    fn detected_as(&self, other: &Anomaly) -> bool {
+        match self.kind() {
+            AnomalyKind::Cluster => other.kind() == AnomalyKind::Cluster && other.start <= self.end && other.end >= self.start,
+            AnomalyKind::Gap => other.kind() == AnomalyKind::Gap && other.start == self.start && other.end == self.end,
+        }
+    }
+
+    /// Creates a cluster from the points occupying the sorted positions `first..first + cluster.len()`.
+    ///
+    /// If `input_indices` maps sorted positions to input positions, the cluster's `elements` are
+    /// re-emitted in input order and their input positions are recorded in `indices`.
+    fn from_sorted(cluster: &[Value], first: usize, input_indices: Option<&[usize]>) -> Self {
+        let mut anomaly: Anomaly = Anomaly::new(cluster);
+        if let Some(input_indices) = input_indices {
+            let mut members: Vec<(usize, Value)> = input_indices[first..first + cluster.len()].iter().copied()
+                .zip(cluster.iter().copied())
+                .collect();
+            members.sort_unstable_by_key(|member: &(usize, Value)| member.0);
+            anomaly.elements = members.iter().map(|member: &(usize, Value)| member.1).collect();
+            anomaly.indices = Some(members.iter().map(|member: &(usize, Value)| member.0).collect());
+        }
+        anomaly
+    }
+
+    /// Creates a gap spanning the empty interval between the two points `start` and `end`.
+    pub fn gap(start: Value, end: Value) -> Self {
+        Anomaly {
+            elements: Vec::new(), // No elements in a gap
+            start,
+            end,
+            span_length: end - start,
+            num_elements: 0,
+            centroid: ((start as f64 + end as f64) / 2.0) as f32,
+            z_score: None,
+            rank_score: None,
+            rank: None,
+            internal_gap_min: None,
+            internal_gap_max: None,
+            internal_gap_mean: None,
+            tightness: None,
+            spacing_deviation: None,
+            core_count: None,
+            border_count: None,
+            empty_start: None,
+            empty_end: None,
+            gap_center: None,
+            duration: None,
+            dead_zone: false,
+            edge: false,
+            weak: false,
+            elements_truncated: false,
+            indices: None,
+            explanation: None,
+            stability: None,
+            p_value: None,
+            significant: None,
         }
     }
 }
 
-#[pyclass]
-pub struct Lyagushka {
-    dataset: Vec<i32>,
-    anomalies: Vec<Anomaly>,
+#[pymethods]
+impl Anomaly {
+
+    /// Whether the anomaly is a cluster or a gap; dead zones and edge gaps are gaps.
+    pub fn kind(&self) -> AnomalyKind {
+        if self.num_elements > 0 { AnomalyKind::Cluster } else { AnomalyKind::Gap }
+    }
+
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// The two types of [`Anomaly`]: clusters of points, and gaps between them.
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum AnomalyKind {
+    Cluster,
+    Gap,
+}
+
+/// Top-level object of the `features` output format.
+#[derive(Serialize)]
+struct FeatureCollection<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    features: Vec<Feature<'a>>,
+}
+
+/// A single anomaly in the `features` output format.
+#[derive(Serialize)]
+struct Feature<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    properties: &'a Anomaly,
+    range: [Value; 2],
+}
+
+/// Position where a cluster or gap begins or ends, in the `boundaries` output format.
+#[derive(Debug, Clone, Serialize)]
+pub struct Boundary {
+    pub position: Value,
+    pub kind: BoundaryKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundaryKind {
+    ClusterStart,
+    ClusterEnd,
+    GapStart,
+    GapEnd,
+}
+
+impl fmt::Display for Anomaly {
+
+    /// Renders a concise one-line summary, e.g. `Cluster[120..145] n=12 z=3.2`
+    /// or `Gap[145..300] span=155 z=-2.1`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.kind() == AnomalyKind::Cluster {
+            let label: &str = if self.weak { "WeakCluster" } else { "Cluster" };
+            write!(f, "{}[{}..{}] n={}", label, self.start, self.end, self.num_elements)?;
+        } else if self.dead_zone {
+            write!(f, "DeadZone[{}..{}] span={}", self.start, self.end, self.span_length)?;
+        } else if self.edge {
+            write!(f, "EdgeGap[{}..{}] span={}", self.start, self.end, self.span_length)?;
+        } else {
+            write!(f, "Gap[{}..{}] span={}", self.start, self.end, self.span_length)?;
+        }
+        if let Some(duration) = &self.duration {
+            write!(f, " ({})", duration)?;
+        }
+        match (self.z_score, self.rank_score) {
+            (Some(z), _) => write!(f, " z={:.1}", z),
+            (None, Some(rank)) => write!(f, " rank={:.2}", rank),
+            (None, None) => write!(f, " z=n/a"),
+        }
+    }
+}
+
+/// Options controlling how anomalies are detected and scored.
+///
+/// `factor` and `min_cluster_size` carry the same meaning as the arguments of
+/// [`Lyagushka::search`]; the remaining fields default to the classic behavior.
+#[pyclass(get_all, set_all)]
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchConfig {
+    pub factor: f32,
+    pub min_cluster_size: usize,
+    /// Standardize `ln(span_length)` of gaps against the other gaps instead of scaling the raw span.
+    pub log_gap_spans: bool,
+    /// Standardize `ln(density)` of clusters against the other clusters instead of the raw
+    /// density, which spans orders of magnitude. A [`Baseline`] density is then a log-density too.
+    pub log_densities: bool,
+    /// Shrink the density of every cluster of `n` points towards the center of all densities by
+    /// `1 / sqrt(n)` of its deviation before scoring it, so that small clusters score less extreme.
+    pub shrinkage: bool,
+    /// Recompute the mean distance without the gaps flagged by a first pass before scanning.
+    pub adaptive: bool,
+    /// Factor for the threshold a distance must stay below to start a cluster, `factor` if unset.
+    pub enter_factor: Option<f32>,
+    /// Factor for the threshold a distance must stay below to continue a cluster, `factor` if unset.
+    /// A smaller exit than entry factor keeps noisy clusters from fragmenting.
+    pub exit_factor: Option<f32>,
+    /// Join points into a cluster when their distance equals the cluster threshold (`<=` instead of `<`).
+    pub inclusive_cluster_threshold: bool,
+    /// Record a gap when its span equals the gap threshold (`>=` instead of `>`).
+    pub inclusive_gap_threshold: bool,
+    /// Report distances that neither join a cluster nor exceed the gap threshold as dead-zone gaps.
+    pub report_dead_zones: bool,
+    /// Re-emit each cluster's `elements` in input order and record their input positions in `indices`.
+    ///
+    /// The input order is captured by the first analysis that enables this option, so it must be
+    /// enabled before any other analysis sorts the dataset.
+    pub input_order: bool,
+    /// Epsilon added to `span_length` in the density denominator, `num_elements / (span_length + eps)`.
+    pub density_smoothing: Option<f32>,
+    /// Flag clusters whose density z-score is below this value as `weak`: they met
+    /// `min_cluster_size` but are sparser than typical clusters. Under [`ScoreMethod::Rank`]
+    /// the threshold is a percentile rank of the density from `0` to `1` instead, so that `0.25`
+    /// flags the sparsest quarter of the clusters.
+    pub weak_cluster_threshold: Option<f32>,
+    /// Report the stretches between the dataset bounds and the first and last anomaly as edge
+    /// gaps when they are wider than the gap threshold.
+    pub edge_gaps: bool,
+    /// Standardize gaps within their own type with the same sign convention as clusters, so that
+    /// both report on a common scale where positive means "more pronounced than usual".
+    pub unified_score: bool,
+    /// How anomalies are scored against the others of their type.
+    ///
+    /// Outside unified mode and without log gap spans, the classic gap score is the span divided
+    /// by the standard deviation of all anomaly spans, while the robust and modified scores
+    /// standardize the span against the median of all anomaly spans.
+    pub score_method: ScoreMethod,
+    /// Attach a human-readable `explanation` of its z-score to every anomaly.
+    pub explain: bool,
+    /// Serialize without indentation and newlines.
+    pub compact: bool,
+    /// String to indent pretty-printed JSON with per level, two spaces if unset.
+    pub indent: Option<String>,
+    /// Serialize as canonical JSON for golden files: object keys in sorted order and floats with
+    /// a fixed number of decimals, so that equal results are byte-identical.
+    pub canonical: bool,
+    /// Skip sorting the dataset, which must then already be in ascending order; the results on
+    /// unsorted data are undefined. With [`ScoreMethod::Classic`], the statistics are also
+    /// accumulated during the scan, with results identical to those of sorted input without it.
+    pub assume_sorted: bool,
+    /// Round centroids to integer positions in the JSON output. This is lossy: the halfway
+    /// centroids of spans with an odd length can no longer be told apart from their neighbours.
+    pub round_centroid: Option<CentroidRounding>,
+    /// Label of the unit the input values are measured in, passed through to the summary.
+    pub units: Option<String>,
+    /// Unit of timestamp input values, to report the span of every anomaly as a `duration`.
+    pub time_unit: Option<TimeUnit>,
+    /// Report the empty interior of every gap, between its bounding data points, as
+    /// `empty_start` and `empty_end`.
+    pub gap_interiors: bool,
+    /// Report the midpoint of every gap between the edges of its neighbouring clusters as `gap_center`.
+    pub gap_centers: bool,
+    /// Count the core and border points of every cluster as `core_count` and `border_count`.
+    pub core_points: bool,
+    /// Rank every scored anomaly among those of its type as `rank`, from `1` for the score
+    /// furthest from average. Scores equally far from average, on either side of it, are ranked
+    /// in ascending order of position.
+    pub rank: bool,
+    /// Relative half-width of the band of factors to score the `stability` of every anomaly
+    /// against, e.g. `0.2` for 80% to 120% of the factors.
+    pub stability_band: Option<f32>,
+    /// Correction for testing every anomaly at once, to flag the `significant` ones.
+    pub correction: Option<Correction>,
+    /// Significance level of the `correction`, the family-wise error rate with Bonferroni and the
+    /// false discovery rate with Benjamini-Hochberg. It must be in `(0, 1)`, which
+    /// [`SearchConfig::validate`] checks.
+    pub alpha: f32,
+    /// Largest number of scans the stability analysis may run, spread evenly across the band.
+    pub max_passes: Option<usize>,
+    /// Seconds since the start of the analysis after which the stability analysis starts no
+    /// further scan. Negative or NaN values are ignored.
+    pub timeout: Option<f32>,
+    /// Fraction in `(0, 1]` of the points to keep in a uniform random sample that is analyzed
+    /// instead of the full dataset. Cluster sizes are scaled back up by the inverse fraction.
+    /// Fractions outside that range, NaN included, are ignored, and [`SearchConfig::validate`]
+    /// rejects them.
+    pub sample: Option<f32>,
+    /// Seed for the sampling, so that the same seed always selects the same points.
+    pub seed: u64,
+    /// Leave out the `elements` of clusters with more than this many points.
+    pub max_elements: Option<usize>,
+    /// Report at most this many clusters, those with the most extreme scores.
+    ///
+    /// With a cap, the analysis stops accumulating anomalies: a first scan only gathers the
+    /// statistics, and a second one scores every anomaly as it is found and keeps no more than
+    /// the most extreme ones, so that the anomalies held never exceed the caps. The robust,
+    /// modified and rank methods and the Benjamini-Hochberg correction need all values at once
+    /// and still keep a number for every anomaly.
+    pub max_clusters: Option<usize>,
+    /// Report and hold at most this many gaps, dead zones included, those with the most extreme
+    /// scores, see `max_clusters`.
+    pub max_gaps: Option<usize>,
+    /// Score clusters by `w * z_density + (1 - w) * z_span` with this weight `w`, where `z_span`
+    /// standardizes the cluster's span against the spans of the other clusters.
+    pub density_weight: Option<f32>,
+    /// Reference statistics to score against instead of those of the current dataset.
+    pub baseline: Option<Baseline>,
+    /// Analyze only the values within this inclusive range, so that the thresholds and statistics
+    /// reflect the region of interest.
+    pub range: Option<(Value, Value)>,
+    /// Treat values at most this far above the first value of their run as coincident with it:
+    /// they are moved onto it before the distances are taken, so that noise below the
+    /// resolution of the data yields zero distances rather than tiny ones. Anchoring on the first
+    /// value keeps a chain of close values from merging into one point across a wide stretch.
+    /// Negative values and instances created from frequencies ignore it.
+    pub epsilon: Option<Value>,
+    /// Inclusive ranges of known sparse regions to exclude from gap detection. Distances between
+    /// consecutive points that reach into a mask are never reported as gaps, dead zones or edge
+    /// gaps and are left out of the mean distance the thresholds are derived from.
+    pub masks: Vec<(Value, Value)>,
+    /// Inclusive ranges where dense clusters are expected. Clusters entirely within one are
+    /// left out of the results, but still count towards the statistics the other clusters are
+    /// scored against and towards the summary.
+    pub expected_dense: Vec<(Value, Value)>,
+    /// Give wider gaps larger positive z-scores instead of larger negative ones. Unified scores
+    /// are positive for wide gaps either way.
+    pub positive_gap_scores: bool,
+    /// Nominal distance between the points of a cluster, to measure the `spacing_deviation` of
+    /// every cluster against.
+    pub expected_spacing: Option<f32>,
+    /// Look for a dominant period in the distances between consecutive points, see
+    /// [`DatasetSummary::detected_period`].
+    pub periodicity: bool,
+    /// Cap the absolute z-scores at this value, keeping their sign, once all statistics are
+    /// calculated. This is meant for display, such as keeping color scales readable.
+    pub clamp_z: Option<f32>,
 }
 
 #[pymethods]
-impl Lyagushka {
-    
+impl SearchConfig {
+
     #[new]
-    pub fn new(dataset: Vec<i32>) -> Self {
-        Lyagushka {
-            dataset,
-            anomalies: vec![]
+    pub fn new(factor: f32, min_cluster_size: usize) -> Self {
+        SearchConfig {
+            factor,
+            min_cluster_size,
+            log_gap_spans: false,
+            log_densities: false,
+            shrinkage: false,
+            adaptive: false,
+            enter_factor: None,
+            exit_factor: None,
+            inclusive_cluster_threshold: true,
+            inclusive_gap_threshold: false,
+            report_dead_zones: false,
+            input_order: false,
+            density_smoothing: None,
+            weak_cluster_threshold: None,
+            edge_gaps: false,
+            unified_score: false,
+            score_method: ScoreMethod::Classic,
+            explain: false,
+            compact: false,
+            indent: None,
+            canonical: false,
+            round_centroid: None,
+            assume_sorted: false,
+            units: None,
+            time_unit: None,
+            gap_interiors: false,
+            gap_centers: false,
+            core_points: false,
+            rank: false,
+            stability_band: None,
+            correction: None,
+            alpha: 0.05,
+            max_passes: None,
+            timeout: None,
+            sample: None,
+            seed: 0,
+            max_elements: None,
+            max_clusters: None,
+            max_gaps: None,
+            density_weight: None,
+            baseline: None,
+            range: None,
+            epsilon: None,
+            masks: Vec::new(),
+            expected_dense: Vec::new(),
+            positive_gap_scores: false,
+            expected_spacing: None,
+            periodicity: false,
+            clamp_z: None,
         }
     }
 
-    fn scan_anomalies(&mut self, factor: f32, min_cluster_size: usize) {
-    
-        // Calculate the mean distance between consecutive points in the dataset.
-        let mean_distance: f32 = self.dataset.windows(2)
-                                        .map(|w| (w[1] - w[0]) as f32)
-                                        .sum::<f32>() / (self.dataset.len() - 1) as f32;
-    
-        // Define thresholds for clustering and gap identification based on the mean distance and factor.
-        let cluster_threshold: f32 = mean_distance / factor;
-        let gap_threshold: f32 = factor * mean_distance;
-    
-        let mut current_cluster: Vec<i32> = Vec::new(); // Temporary storage for points in the current cluster.
-    
-        // Iterate through pairs of consecutive points to find clusters and significant gaps.
-        for window in self.dataset.windows(2) {
-            let gap_size: f32 = (window[1] - window[0]) as f32;
-    
-            if gap_size <= cluster_threshold {
-                // Add points to the current cluster
-                if current_cluster.is_empty() {
-                    current_cluster.push(window[0]); // Start a new cluster with the first point
-                }
-                current_cluster.push(window[1]); // Add the second point to the cluster
-            } else {
-                // End the current cluster and start a new gap
-                if !current_cluster.is_empty() && current_cluster.len() >= min_cluster_size {
-                    self.anomalies.push(Anomaly::new(&current_cluster));
-                    current_cluster.clear();
-                }
-    
-                // Record the gap
-                if gap_size > gap_threshold {
-                    self.anomalies.push(Anomaly {
-                        elements: Vec::new(), // No elements in a gap
-                        start: window[0],
-                        end: window[1],
-                        span_length: gap_size as i32,
-                        num_elements: 0,
-                        centroid: (window[0] as f32 + window[1] as f32) / 2.0,
-                        z_score: None,
-                    });
-                }
+    /// Creates a configuration from a `sensitivity` between `0` and `1` instead of a factor,
+    /// where a higher sensitivity reports at least as many gaps and looser clusters.
+    ///
+    /// The sensitivity maps to `factor = 4^(1 - sensitivity)`: `1` gives `factor = 1`, where both
+    /// thresholds equal the mean distance, `0.5` gives `2` and `0` gives `4`. Values outside
+    /// `[0, 1]` are clamped. Factors below `1` are never produced, as they widen the effective
+    /// gap threshold again.
+    #[staticmethod]
+    pub fn with_sensitivity(sensitivity: f32, min_cluster_size: usize) -> Self {
+        SearchConfig::new(4f32.powf(1.0 - sensitivity.clamp(0.0, 1.0)), min_cluster_size)
+    }
+
+    /// Checks that the options with a restricted range of values are within it: the `sample`
+    /// fraction in `(0, 1]` and the `alpha` level in `(0, 1)`.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.sample.is_some_and(|fraction: f32| !(fraction > 0.0 && fraction <= 1.0)) {
+            return Err(Error::InvalidOption { name: "sample", requirement: "the fraction must be in (0, 1]" });
+        }
+        if !(self.alpha > 0.0 && self.alpha < 1.0) {
+            return Err(Error::InvalidOption { name: "alpha", requirement: "the level must be in (0, 1)" });
+        }
+        Ok(())
+    }
+}
+
+impl SearchConfig {
+
+    /// Whether any of the masks reaches strictly between `start` and `end`.
+    fn is_masked(&self, start: Value, end: Value) -> bool {
+        self.masks.iter().any(|&(lo, hi): &(Value, Value)| start < hi && lo < end)
+    }
+}
+
+/// The statistics anomalies are standardized against, e.g. those of historical "normal" data
+/// to score new data against, see [`SearchConfig::baseline`] and [`Lyagushka::baseline`].
+///
+/// The span statistics describe what gaps are scored by: the mean and standard deviation of
+/// the gap spans in unified mode, of their logarithm with `log_gap_spans`, and otherwise of
+/// the spans of all anomalies, of which the classic score method uses only the standard
+/// deviation.
+#[pyclass(get_all, set_all)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Baseline {
+    pub mean_density: f32,
+    pub std_dev_density: f32,
+    pub mean_span: f32,
+    pub std_dev_span: f32,
+}
+
+#[pymethods]
+impl Baseline {
+
+    #[new]
+    pub fn new(mean_density: f32, std_dev_density: f32, mean_span: f32, std_dev_span: f32) -> Self {
+        Baseline { mean_density, std_dev_density, mean_span, std_dev_span }
+    }
+}
+
+/// Dataset-level statistics and metadata of the last analysis.
+#[pyclass(get_all)]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DatasetSummary {
+    /// The [`ALGORITHM_VERSION`] of the analysis, `0` before any has run.
+    pub algorithm_version: u32,
+    /// Unit of the input values, and therefore of `start`, `end` and `span_length`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub units: Option<String>,
+    pub num_points: usize,
+    pub mean_distance: f32,
+    pub cluster_threshold: f32,
+    pub cluster_exit_threshold: f32,
+    pub gap_threshold: f32,
+    pub num_clusters: usize,
+    pub num_gaps: usize,
+    pub num_dead_zones: usize,
+    /// Candidate clusters dropped for having fewer than `min_cluster_size` points.
+    pub num_discarded_clusters: usize,
+    /// Distance after which the pattern of consecutive distances repeats, if periodicity
+    /// detection was requested and found one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detected_period: Option<f32>,
+    /// Mean and standard deviation of the distances between the centroids of consecutive
+    /// clusters, if there are at least two.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_inter_cluster_gap: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub std_dev_inter_cluster_gap: Option<f32>,
+    /// Number of scans the stability analysis ran, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stability_passes: Option<usize>,
+    /// Whether `max_passes` or `timeout` cut the stability analysis short of its full number
+    /// of scans.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub pass_limit_reached: bool,
+    /// Whether `max_clusters` or `max_gaps` left out anomalies from the output. The counts above
+    /// still include them.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+/// Top-level object of the `report` output format.
+#[derive(Serialize)]
+struct Report<'a> {
+    summary: &'a DatasetSummary,
+    anomalies: &'a [Anomaly],
+}
+
+/// Top-level object of the `bundle` output format.
+#[derive(Serialize)]
+struct Bundle<'a> {
+    algorithm_version: u32,
+    dataset: &'a [Value],
+    parameters: Option<&'a SearchConfig>,
+    anomalies: &'a [Anomaly],
+}
+
+/// Histogram of the distances between consecutive points, with the thresholds overlaid.
+#[derive(Debug, Clone, Serialize)]
+pub struct DistanceHistogram {
+    pub cluster_threshold: f32,
+    pub cluster_exit_threshold: f32,
+    pub gap_threshold: f32,
+    pub buckets: Vec<HistogramBucket>,
+}
+
+/// Number of distances in `[start, end)`, or `[start, end]` for the last bucket.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramBucket {
+    pub start: f32,
+    pub end: f32,
+    pub count: usize,
+}
+
+/// Number of points in the bin `[start, end)`, or `[start, end]` for the last bin, of a
+/// [`Lyagushka::bin_counts`] analysis.
+#[derive(Debug, Clone, Serialize)]
+pub struct BinCount {
+    pub start: Value,
+    pub end: Value,
+    pub count: usize,
+    /// `count` per unit of bin width.
+    pub density: f32,
+    /// Score of the density against those of the other bins.
+    pub z_score: Option<f32>,
+    /// Set for bins whose density is unusual enough to be reported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<BinKind>,
+}
+
+/// Clusters of a [`Lyagushka::two_scale_clusters`] analysis, nested at two scales.
+#[derive(Debug, Clone, Serialize)]
+pub struct TwoScaleClusters {
+    /// The coarse clusters, each made of at least two fine clusters.
+    pub groups: Vec<ClusterGroup>,
+    /// The fine clusters that belong to no coarse cluster.
+    pub ungrouped: Vec<Anomaly>,
+}
+
+/// Coarse cluster of a [`Lyagushka::two_scale_clusters`] analysis: a group of fine clusters
+/// whose centroids lie close together.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterGroup {
+    /// The `start` of the first of the `clusters`.
+    pub start: Value,
+    /// The `end` of the last of the `clusters`.
+    pub end: Value,
+    /// Number of points in all of the `clusters`.
+    pub num_elements: usize,
+    /// Score of the group among the centroids of all fine clusters.
+    pub z_score: Option<f32>,
+    /// The fine clusters of the group, in ascending order.
+    pub clusters: Vec<Anomaly>,
+}
+
+/// How the density of a bin departs from the mean bin density, mirroring clusters and gaps:
+/// dense bins hold at least `min_cluster_size` points at more than `factor` times the mean
+/// density, sparse bins less than the mean density divided by `factor`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinKind {
+    Dense,
+    Sparse,
+}
+
+/// Local point density at a sampled position, in the `profile` output format.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfilePoint {
+    pub position: f32,
+    /// Points per unit within the window around `position`.
+    pub density: f32,
+}
+
+/// Errors reported by the library.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A projection was requested on an instance not created from two-dimensional points.
+    NotTwoDimensional,
+    /// The dataset was required to be sorted, but the value at `index` is smaller than its predecessor.
+    Unsorted { index: usize },
+    /// The anomalies at positions `first` and `second` share interior points.
+    Overlap { first: usize, second: usize },
+    /// The distance between the smallest value `min` and the largest value `max` does not fit
+    /// into a [`Value`], so spans and gaps across it cannot be represented.
+    RangeOverflow { min: Value, max: Value },
+    /// The text parsed into a dataset holds `content` on line `line`, counted from one, which
+    /// is neither blank nor a number.
+    InvalidLine { line: usize, content: String },
+    /// Line `line` of the parsed text holds `content`, an integer outside the range of [`Value`].
+    OutOfRangeLine { line: usize, content: String },
+    /// Line `line` of weighted text input holds `content`, a value without a valid frequency.
+    MissingFrequency { line: usize, content: String },
+    /// The parsed text holds NaN or an infinity on the `lines`, and [`NonFinitePolicy::Error`]
+    /// was in effect.
+    NonFinite { lines: Vec<usize> },
+    /// The parameter `name` of [`generate_clusters`] is negative.
+    NegativeParameter { name: &'static str },
+    /// The points [`generate_clusters`] was asked for would not all fit into an `i32`.
+    GeneratedRangeOverflow,
+    /// The option `name` of a [`SearchConfig`] holds a value that does not satisfy `requirement`.
+    InvalidOption { name: &'static str, requirement: &'static str },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotTwoDimensional => write!(f, "Dataset is not two-dimensional"),
+            Error::Unsorted { index } => write!(f, "Dataset is not sorted: value at index {} is smaller than its predecessor", index),
+            Error::Overlap { first, second } => write!(f, "Anomalies {} and {} overlap", first, second),
+            Error::RangeOverflow { min, max } => write!(f, "Dataset range from {} to {} is too wide to measure", min, max),
+            Error::InvalidLine { line, content } => write!(f, "Line {}: {} is not a number", line, content),
+            Error::OutOfRangeLine { line, content } => {
+                write!(f, "Line {}: {} is out of range for the value type ({}..={})", line, content, Value::MIN, Value::MAX)
             }
+            Error::MissingFrequency { line, content } => write!(f, "Line {}: {} has no valid frequency", line, content),
+            Error::NonFinite { lines } => {
+                let lines: Vec<String> = lines.iter().map(usize::to_string).collect();
+                write!(f, "Non-finite values (NaN or infinity) on lines {}", lines.join(", "))
+            }
+            Error::NegativeParameter { name } => write!(f, "The {} must not be negative", name),
+            Error::GeneratedRangeOverflow => write!(f, "The generated values would exceed the range of 32-bit integers"),
+            Error::InvalidOption { name, requirement } => write!(f, "Invalid value for {}: {}", name, requirement),
         }
-    
-        // Finalize the last cluster if applicable
-        if !current_cluster.is_empty() && current_cluster.len() >= min_cluster_size {
-            self.anomalies.push(Anomaly::new(&current_cluster));
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for PyErr {
+    fn from(err: Error) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// The handling of non-finite input values, NaN and infinities as exported for missing float
+/// readings, see [`ParseOptions::on_nonfinite`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum NonFinitePolicy {
+    /// Fail with an error listing the lines that hold them.
+    #[default]
+    Error,
+    /// Skip them, listing their lines in [`ParsedInput::nonfinite`].
+    Drop,
+}
+
+impl std::str::FromStr for NonFinitePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(NonFinitePolicy::Error),
+            "drop" => Ok(NonFinitePolicy::Drop),
+            _ => Err(format!("Unknown non-finite policy: {}", s)),
         }
-    
     }
+}
 
-    pub fn search(&mut self, factor: f32, min_cluster_size: usize) -> String {
+/// How the lines of text input are turned into values, see [`parse_lines`].
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Fail on lines that are not valid values instead of skipping them.
+    pub strict: bool,
+    /// Strip digit grouping separators (`,`, `_` and spaces) before parsing.
+    pub grouped: bool,
+    /// Read each line as JSON: a number, or an object holding the number in this field.
+    pub jsonl_field: Option<String>,
+    /// Read each line as a value followed by its frequency, the number of points at the value,
+    /// separated by whitespace or a comma.
+    pub weighted: bool,
+    /// What to do with lines holding NaN or an infinity.
+    pub on_nonfinite: NonFinitePolicy,
+    /// Skip lines starting with this prefix, even with `strict`. Defaults to `#`.
+    pub comment_prefix: Option<String>,
+}
 
-        // Sort the vector
-        self.dataset.sort_unstable();
-    
-        // Calculate clusters and gaps from the dataset using predefined criteria.
-        self.scan_anomalies(factor, min_cluster_size);
-    
-        // Calculate the mean density of clusters in the dataset for comparison.
-        let mean_density: f32 = self.anomalies.iter()
-            .filter(|info: &&Anomaly| info.num_elements > 0)
-            .map(|info: &Anomaly| info.num_elements as f32 / info.span_length as f32)
-            .sum::<f32>() / self.anomalies.iter().filter(|info: &&Anomaly| info.num_elements > 0).count() as f32;
-    
-        // Calculate the standard deviation of cluster densities to evaluate variation.
-        let variance_density: f32 = self.anomalies.iter()
-            .filter(|info: &&Anomaly| info.num_elements > 0)
-            .map(|info: &Anomaly| info.num_elements as f32 / info.span_length as f32)
-            .map(|density: f32| (density - mean_density).powi(2))
-            .sum::<f32>() / self.anomalies.iter().filter(|info: &&Anomaly| info.num_elements > 0).count() as f32;
-        let std_dev_density: f32 = variance_density.sqrt();
-    
-        // Calculate mean span length
-        let mean_span_length: f32 = self.anomalies.iter()
-            .map(|info: &Anomaly| info.span_length as f32)
-            .sum::<f32>() / self.anomalies.len() as f32;
-    
-        // Calculate variance
-        let variance: f32 = self.anomalies.iter()
-            .map(|info: &Anomaly| (info.span_length as f32 - mean_span_length).powi(2))
-            .sum::<f32>() / self.anomalies.len() as f32;
-    
-        // Standard deviation is the square root of variance
-        let std_dev_span_length: f32 = variance.sqrt();
-    
-        // Update Z-scores for both clusters and gaps based on their deviation from mean metrics.
-        for info in self.anomalies.iter_mut() {
-            if info.num_elements > 0 {
-                // Calculate and update Z-score for clusters based on density deviation.
-                let cluster_density: f32 = info.num_elements as f32 / info.span_length as f32;
-                info.z_score = Some((cluster_density - mean_density) / std_dev_density);
-            } else {
-                // Calculate and update Z-score for gaps based on span length deviation.
-                info.z_score = Some((info.span_length as f32 / std_dev_span_length) * -1.0);
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            strict: false,
+            grouped: false,
+            jsonl_field: None,
+            weighted: false,
+            on_nonfinite: NonFinitePolicy::default(),
+            comment_prefix: Some("#".to_string()),
+        }
+    }
+}
+
+/// The values of text input and the lines that were skipped rather than failing, see
+/// [`parse_lines`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedInput {
+    /// Every value with its frequency: the second column of weighted input, and otherwise `1`.
+    pub values: Vec<(Value, usize)>,
+    /// The numbers, counted from one, of the lines holding integers outside the range of
+    /// [`Value`].
+    pub out_of_range: Vec<usize>,
+    /// The numbers of the lines holding NaN or an infinity, dropped with [`NonFinitePolicy::Drop`].
+    pub nonfinite: Vec<usize>,
+}
+
+/// Parses one value per line, skipping blank lines and comments, as the command-line tool reads
+/// its input.
+///
+/// Integers too large or too small for [`Value`] are skipped but listed in the result, so that
+/// a file of too-large numbers can be told apart from an empty one. With `strict`, they and any
+/// other line that is not a valid value are an error naming the line instead. Lines holding
+/// NaN or an infinity are an error naming all of them, unless `on_nonfinite` drops them.
+pub fn parse_lines<S: AsRef<str>>(lines: impl IntoIterator<Item = S>, options: &ParseOptions) -> Result<ParsedInput, Error> {
+    let mut input: ParsedInput = ParsedInput::default();
+    for (number, line) in lines.into_iter().enumerate().map(|(index, line): (usize, S)| (index + 1, line)) {
+        let line: &str = line.as_ref().trim();
+        if line.is_empty() || options.comment_prefix.as_deref().is_some_and(|prefix: &str| line.starts_with(prefix)) {
+            continue;
+        }
+        // The value column of a weighted line is parsed like a whole line otherwise.
+        let (column, frequency): (&str, Option<usize>) = match line.split_once(|c: char| c.is_whitespace() || c == ',') {
+            Some((value, frequency)) if options.weighted => (value, frequency.trim().parse().ok()),
+            _ if options.weighted => (line, None),
+            _ => (line, Some(1)),
+        };
+        let token: String = match &options.jsonl_field {
+            Some(field) => match serde_json::from_str::<serde_json::Value>(line) {
+                Ok(serde_json::Value::Number(value)) => value.to_string(),
+                Ok(serde_json::Value::Object(object)) => match object.get(field) {
+                    Some(serde_json::Value::Number(value)) => value.to_string(),
+                    _ => String::new(),
+                },
+                _ => String::new(),
+            },
+            None if options.grouped => column.chars().filter(|c: &char| !matches!(c, ',' | '_' | ' ')).collect(),
+            None => column.to_string(),
+        };
+        let content = || line.to_string();
+        match (token.parse::<Value>(), frequency) {
+            (Ok(value), Some(frequency)) => input.values.push((value, frequency)),
+            (Ok(_), None) if options.strict => return Err(Error::MissingFrequency { line: number, content: content() }),
+            (Ok(_), None) => {}
+            (Err(err), _) if matches!(err.kind(), std::num::IntErrorKind::PosOverflow | std::num::IntErrorKind::NegOverflow) => {
+                if options.strict {
+                    return Err(Error::OutOfRangeLine { line: number, content: content() });
+                }
+                input.out_of_range.push(number);
             }
+            (Err(_), _) if token.parse::<f64>().is_ok_and(|value: f64| !value.is_finite()) => input.nonfinite.push(number),
+            (Err(_), _) if options.strict => return Err(Error::InvalidLine { line: number, content: content() }),
+            (Err(_), _) => {}
         }
-    
-        serde_json::to_string_pretty(&self.anomalies).unwrap_or_else(|_| "Failed to serialize data".to_string())
     }
+    if !input.nonfinite.is_empty() && options.on_nonfinite == NonFinitePolicy::Error {
+        return Err(Error::NonFinite { lines: input.nonfinite });
+    }
+    Ok(input)
 }
 
-#[pymodule]
-fn lyagushka(m: &Bound<'_, PyModule>) -> PyResult<()> {
-    m.add_class::<Lyagushka>()?;
-    Ok(())
-}
\ No newline at end of file
+/// Moves every value of the sorted `dataset` that is at most `epsilon` above the first value of
+/// its run onto that value, see [`SearchConfig::epsilon`].
+fn snap_coincident(dataset: &[Value], epsilon: Value) -> Vec<Value> {
+    let mut anchor: Option<Value> = None;
+    dataset.iter()
+        .map(|&value: &Value| match anchor {
+            Some(first) if value - first <= epsilon => first,
+            _ => *anchor.insert(value),
+        })
+        .collect()
+}
+
+/// Returns the mean and population standard deviation of `values`, both NaN if there are none,
+/// see [`lyagushka_core::mean_and_variance`].
+fn mean_and_std_dev(values: &[f32]) -> (f32, f32) {
+    let (mean, variance) = mean_and_variance(values);
+    (mean, variance.sqrt())
+}
+
+/// Returns the number of `elements` with at least `min_points` elements, themselves included,
+/// closer than `threshold`, or as close with `inclusive`.
+fn count_core_points(elements: &[Value], threshold: f32, inclusive: bool, min_points: usize) -> usize {
+    let mut sorted: Vec<Value> = elements.to_vec();
+    sorted.sort_unstable();
+    let near = |a: Value, b: Value| {
+        let distance: f32 = (b - a) as f32;
+        distance < threshold || (inclusive && distance == threshold)
+    };
+    // The neighbours of the sorted elements form a window that only ever moves forward. Each
+    // element is kept in its own window even at a zero threshold.
+    let (mut first, mut last): (usize, usize) = (0, 0);
+    let mut core_count: usize = 0;
+    for (position, &value) in sorted.iter().enumerate() {
+        while first < position && !near(sorted[first], value) {
+            first += 1;
+        }
+        last = last.max(position);
+        while last + 1 < sorted.len() && near(value, sorted[last + 1]) {
+            last += 1;
+        }
+        if last - first + 1 >= min_points {
+            core_count += 1;
+        }
+    }
+    core_count
+}
+
+/// The `capacity` anomalies of a stream with the scores furthest from `average`, see
+/// [`SearchConfig::max_clusters`]. Of equally extreme anomalies the first in position are kept,
+/// and unscored ones are the first to go.
+struct MostExtreme {
+    capacity: usize,
+    average: f32,
+    /// The kept anomalies, the least extreme on top.
+    heap: BinaryHeap<Reverse<Candidate>>,
+    /// Whether any anomaly was left out.
+    dropped: bool,
+}
+
+impl MostExtreme {
+    /// Creates an empty selection of at most `capacity` anomalies, or of all without one.
+    fn new(capacity: Option<usize>, average: f32) -> Self {
+        MostExtreme { capacity: capacity.unwrap_or(usize::MAX), average, heap: BinaryHeap::new(), dropped: false }
+    }
+
+    /// Keeps `info` if it is among the most extreme so far, evicting the least extreme one.
+    fn push(&mut self, info: Anomaly) {
+        let candidate: Candidate = Candidate { extremity: info.extremity(self.average).unwrap_or(f32::NEG_INFINITY), info };
+        if self.heap.len() < self.capacity {
+            self.heap.push(Reverse(candidate));
+            return;
+        }
+        self.dropped = true;
+        if let Some(mut least) = self.heap.peek_mut().filter(|least: &PeekMut<Reverse<Candidate>>| candidate > least.0) {
+            *least = Reverse(candidate);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Returns the kept anomalies, in no particular order.
+    fn into_anomalies(self) -> impl Iterator<Item = Anomaly> {
+        self.heap.into_iter().map(|Reverse(candidate): Reverse<Candidate>| candidate.info)
+    }
+}
+
+/// An anomaly of a [`MostExtreme`] selection, ordered by how much it deserves to be kept: the
+/// more extreme first, then the first in position.
+struct Candidate {
+    extremity: f32,
+    info: Anomaly,
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.extremity.total_cmp(&other.extremity).then_with(|| other.info.sort_key().cmp(&self.info.sort_key()))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+/// Sets the `gap_center` of gaps ordered by position from the clusters around them, which are
+/// fed in order of position, see [`SearchConfig::gap_centers`].
+struct GapCenters<'a> {
+    gaps: Vec<&'a mut Anomaly>,
+    /// The first gap that has not met the cluster after it yet.
+    next: usize,
+    /// The end of the last cluster so far.
+    last_end: Option<Value>,
+}
+
+impl<'a> GapCenters<'a> {
+    fn new(anomalies: &'a mut [Anomaly]) -> Self {
+        let gaps: Vec<&'a mut Anomaly> = anomalies.iter_mut().filter(|info: &&mut Anomaly| info.kind() == AnomalyKind::Gap).collect();
+        GapCenters { gaps, next: 0, last_end: None }
+    }
+
+    /// Places the gaps that end before the cluster from `start` to `end` between it and the
+    /// cluster before.
+    fn cluster(&mut self, start: Value, end: Value) {
+        while self.gaps.get(self.next).is_some_and(|gap: &&mut Anomaly| gap.end <= start) {
+            GapCenters::center(self.gaps[self.next], self.last_end, Some(start));
+            self.next += 1;
+        }
+        self.last_end = Some(end);
+    }
+
+    /// Places the gaps after the last cluster.
+    fn finish(self) {
+        for gap in self.gaps.into_iter().skip(self.next) {
+            GapCenters::center(gap, self.last_end, None);
+        }
+    }
+
+    /// Centers `gap` between the `left` and `right` clusters, where a missing one is replaced by
+    /// the bound of the gap itself. A gap with no cluster on either side has no center.
+    fn center(gap: &mut Anomaly, left: Option<Value>, right: Option<Value>) {
+        if left.is_some() || right.is_some() {
+            let (left, right): (Value, Value) = (left.unwrap_or(gap.start), right.unwrap_or(gap.end));
+            gap.gap_center = Some(((left as f64 + right as f64) / 2.0) as f32);
+        }
+    }
+}
+
+/// Returns the median and the median absolute deviation of `values`, both NaN if there are none.
+fn median_and_mad(values: &[f32]) -> (f32, f32) {
+    let median = |values: &mut Vec<f32>| -> f32 {
+        values.sort_unstable_by(f32::total_cmp);
+        match values.len() {
+            0 => f32::NAN,
+            len if len % 2 == 0 => (values[len / 2 - 1] + values[len / 2]) / 2.0,
+            len => values[len / 2],
+        }
+    };
+    let center: f32 = median(&mut values.to_vec());
+    let mad: f32 = median(&mut values.iter().map(|value: &f32| (value - center).abs()).collect());
+    (center, mad)
+}
+
+/// Returns the mid-rank percentile of `value` among `values`: the fraction of them below it,
+/// counting equal ones half. `None` if there is nothing to rank against.
+fn percentile_rank(value: f32, values: &[f32]) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    let below: usize = values.iter().filter(|other: &&f32| **other < value).count();
+    let equal: usize = values.iter().filter(|other: &&f32| **other == value).count();
+    Some((below as f32 + equal as f32 / 2.0) / values.len() as f32)
+}
+
+/// Returns the z-score of `value`. A standard deviation of zero means all values were equal,
+/// so the value is scored as average; an undefined one means there was nothing to compare with.
+fn standardize(value: f32, mean: f32, std_dev: f32) -> Option<f32> {
+    if std_dev > 0.0 {
+        Some((value - mean) / std_dev)
+    } else if std_dev == 0.0 {
+        Some(0.0)
+    } else {
+        None
+    }
+}
+
+/// Python iterator over the anomalies of the last analysis.
+#[pyclass]
+pub struct AnomalyIterator {
+    inner: std::vec::IntoIter<Anomaly>,
+}
+
+#[pymethods]
+impl AnomalyIterator {
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<Anomaly> {
+        slf.inner.next()
+    }
+}
+
+/// Index over the anomalies of an analysis for repeated containment queries, see
+/// [`Lyagushka::index`].
+///
+/// The anomalies are kept ordered by `start` next to the running maximum of their `end`, so a
+/// query binary-searches the last anomaly starting at or before the queried position and walks
+/// back only as long as an earlier anomaly can still reach it.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct AnomalyIndex {
+    anomalies: Vec<Anomaly>,
+    max_ends: Vec<Value>,
+}
+
+impl AnomalyIndex {
+
+    /// Builds the index over `anomalies`, in any order.
+    pub fn new(mut anomalies: Vec<Anomaly>) -> Self {
+        anomalies.sort_by_key(Anomaly::sort_key);
+        let max_ends: Vec<Value> = anomalies.iter()
+            .scan(Value::MIN, |max_end: &mut Value, info: &Anomaly| {
+                *max_end = (*max_end).max(info.end);
+                Some(*max_end)
+            })
+            .collect();
+        AnomalyIndex { anomalies, max_ends }
+    }
+
+    /// Returns the anomalies whose closed range `[start, end]` contains `value`, ordered by `start`.
+    pub fn query(&self, value: Value) -> Vec<&Anomaly> {
+        self.query_range(value, value)
+    }
+
+    /// Returns the anomalies whose closed range `[start, end]` overlaps `[lo, hi]`, ordered by `start`.
+    pub fn query_range(&self, lo: Value, hi: Value) -> Vec<&Anomaly> {
+        let upto: usize = self.anomalies.partition_point(|info: &Anomaly| info.start <= hi);
+        let from: usize = self.max_ends[..upto].partition_point(|&max_end: &Value| max_end < lo);
+        self.anomalies[from..upto].iter()
+            .filter(|info: &&Anomaly| info.end >= lo)
+            .collect()
+    }
+}
+
+#[pymethods]
+impl AnomalyIndex {
+
+    /// Python wrapper around [`AnomalyIndex::query`].
+    #[pyo3(name = "query")]
+    fn py_query(&self, value: Value) -> Vec<Anomaly> {
+        self.query(value).into_iter().cloned().collect()
+    }
+
+    /// Python wrapper around [`AnomalyIndex::query_range`].
+    #[pyo3(name = "query_range")]
+    fn py_query_range(&self, lo: Value, hi: Value) -> Vec<Anomaly> {
+        self.query_range(lo, hi).into_iter().cloned().collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.anomalies.len()
+    }
+}
+
+/// Axis of a two-dimensional dataset to project onto.
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+/// The formulas anomalies can be scored by, see [`SearchConfig::score_method`].
+///
+/// The location-scale methods standardize a value as `(value - location) / scale`; a supplied
+/// [`Baseline`] holds the location and scale of the selected method.
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ScoreMethod {
+    /// Standardize against the mean and standard deviation.
+    Classic,
+    /// Standardize against the median and the median absolute deviation (MAD), scaled by
+    /// `1.4826` to match the standard deviation of normally distributed data, so that a few
+    /// extreme anomalies don't mask the others.
+    Robust,
+    /// The modified z-score of Iglewicz and Hoaglin, `0.6745 * (value - median) / MAD`. If more
+    /// than half of the values are equal, so that the MAD is zero, the mean absolute deviation
+    /// from the median scaled by `1.2533` takes its place.
+    Modified,
+    /// The percentile rank among the others of the type, from `0` to `1` with `0.5` for the
+    /// median, which does not assume any distribution. It is reported as
+    /// [`Anomaly::rank_score`], leaving `z_score` empty, and ignores a supplied baseline.
+    Rank,
+}
+
+impl ScoreMethod {
+    /// Returns the location and scale of `values` to standardize against, both NaN if there
+    /// are none.
+    fn location_and_scale(self, values: &[f32]) -> (f32, f32) {
+        match self {
+            ScoreMethod::Classic | ScoreMethod::Rank => mean_and_std_dev(values),
+            ScoreMethod::Robust => {
+                let (median, mad) = median_and_mad(values);
+                (median, 1.4826 * mad)
+            }
+            ScoreMethod::Modified => {
+                let (median, mad) = median_and_mad(values);
+                if mad == 0.0 {
+                    let mean_absolute_deviation: f32 = values.iter()
+                        .map(|value: &f32| (value - median).abs())
+                        .sum::<f32>() / values.len() as f32;
+                    (median, 1.2533 * mean_absolute_deviation)
+                } else {
+                    (median, mad / 0.6745)
+                }
+            }
+        }
+    }
+
+    /// Scores `value` against `values`, whose location and scale are given.
+    fn score(self, value: f32, values: &[f32], location: f32, scale: f32) -> Option<f32> {
+        match self {
+            ScoreMethod::Rank => percentile_rank(value, values),
+            _ => standardize(value, location, scale),
+        }
+    }
+
+    /// The score of an anomaly that is exactly average.
+    fn average(self) -> f32 {
+        match self {
+            ScoreMethod::Rank => 0.5,
+            _ => 0.0,
+        }
+    }
+}
+
+impl std::str::FromStr for ScoreMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(ScoreMethod::Classic),
+            "robust" => Ok(ScoreMethod::Robust),
+            "modified" => Ok(ScoreMethod::Modified),
+            "rank" => Ok(ScoreMethod::Rank),
+            _ => Err(format!("Unknown score method: {}", s)),
+        }
+    }
+}
+
+/// The units timestamps can be counted in, see [`SearchConfig::time_unit`].
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum TimeUnit {
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+    Minutes,
+    Hours,
+    Days,
+}
+
+impl TimeUnit {
+    /// Length of the unit in nanoseconds.
+    fn nanoseconds(self) -> i128 {
+        match self {
+            TimeUnit::Nanoseconds => 1,
+            TimeUnit::Microseconds => 1_000,
+            TimeUnit::Milliseconds => 1_000_000,
+            TimeUnit::Seconds => 1_000_000_000,
+            TimeUnit::Minutes => 60 * 1_000_000_000,
+            TimeUnit::Hours => 3_600 * 1_000_000_000,
+            TimeUnit::Days => 86_400 * 1_000_000_000,
+        }
+    }
+
+    /// Formats `span` units as their non-zero days, hours, minutes, seconds and sub-seconds
+    /// from largest to smallest, e.g. `1h 30m` for 5400 seconds and `0s` for nothing.
+    fn format_duration(self, span: Value) -> String {
+        const PARTS: [(&str, i128); 7] = [
+            ("d", 86_400 * 1_000_000_000),
+            ("h", 3_600 * 1_000_000_000),
+            ("m", 60 * 1_000_000_000),
+            ("s", 1_000_000_000),
+            ("ms", 1_000_000),
+            ("us", 1_000),
+            ("ns", 1),
+        ];
+        // A no-op cast with `bigint`, where values already are 128 bits wide.
+        #[allow(clippy::unnecessary_cast)]
+        let mut remaining: i128 = (span as i128).saturating_mul(self.nanoseconds());
+        let sign: &str = if remaining < 0 { "-" } else { "" };
+        remaining = remaining.saturating_abs();
+        let mut parts: Vec<String> = Vec::new();
+        for (suffix, length) in PARTS {
+            if remaining >= length {
+                parts.push(format!("{}{}", remaining / length, suffix));
+                remaining %= length;
+            }
+        }
+        if parts.is_empty() {
+            "0s".to_string()
+        } else {
+            format!("{}{}", sign, parts.join(" "))
+        }
+    }
+}
+
+impl std::str::FromStr for TimeUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ns" => Ok(TimeUnit::Nanoseconds),
+            "us" => Ok(TimeUnit::Microseconds),
+            "ms" => Ok(TimeUnit::Milliseconds),
+            "s" => Ok(TimeUnit::Seconds),
+            "min" => Ok(TimeUnit::Minutes),
+            "h" => Ok(TimeUnit::Hours),
+            "d" => Ok(TimeUnit::Days),
+            _ => Err(format!("Unknown time unit: {}", s)),
+        }
+    }
+}
+
+/// How [`SearchConfig::round_centroid`] turns centroids into integer positions.
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum CentroidRounding {
+    /// Round halfway centroids away from zero.
+    Nearest,
+    Floor,
+    Ceil,
+}
+
+impl CentroidRounding {
+    fn apply(self, centroid: f32) -> i64 {
+        match self {
+            CentroidRounding::Nearest => centroid.round() as i64,
+            CentroidRounding::Floor => centroid.floor() as i64,
+            CentroidRounding::Ceil => centroid.ceil() as i64,
+        }
+    }
+}
+
+impl std::str::FromStr for CentroidRounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(CentroidRounding::Nearest),
+            "floor" => Ok(CentroidRounding::Floor),
+            "ceil" => Ok(CentroidRounding::Ceil),
+            _ => Err(format!("Unknown centroid rounding: {}", s)),
+        }
+    }
+}
+
+/// Multiple-comparison corrections selectable for [`SearchConfig::correction`].
+///
+/// Every anomaly with a z-score counts as one test, with the two-sided normal p-value of its
+/// score. The more anomalies a large dataset yields, the stricter the correction becomes.
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Correction {
+    /// Significant if `p * m <= alpha` for `m` tests, bounding the chance of any false positive.
+    Bonferroni,
+    /// Significant if `p` is at most the largest `p_(k)` with `p_(k) <= k / m * alpha`, for the
+    /// p-values sorted in ascending order, bounding the expected share of false positives.
+    BenjaminiHochberg,
+}
+
+impl Correction {
+    /// Sets the `p_value` and `significant` flag of every anomaly with a z-score, each of them
+    /// one of the tests.
+    fn flag_significant(self, anomalies: &mut [Anomaly], alpha: f32) {
+        let mut p_values: Vec<f32> = anomalies.iter().filter_map(|info: &Anomaly| info.z_score.map(two_sided_p_value)).collect();
+        let cutoff: f32 = self.cutoff(alpha, p_values.len(), &mut p_values);
+        Correction::flag_at(anomalies, cutoff);
+    }
+
+    /// Returns the largest p-value that is significant at `alpha` among `tests` tests. Of their
+    /// `p_values`, Bonferroni needs none and Benjamini-Hochberg every one.
+    fn cutoff(self, alpha: f32, tests: usize, p_values: &mut [f32]) -> f32 {
+        let tests: f32 = tests as f32;
+        match self {
+            Correction::Bonferroni => alpha / tests,
+            Correction::BenjaminiHochberg => {
+                p_values.sort_unstable_by(f32::total_cmp);
+                p_values.iter().enumerate().rev()
+                    .find(|&(rank, p): &(usize, &f32)| *p <= (rank + 1) as f32 / tests * alpha)
+                    .map_or(f32::NEG_INFINITY, |(_, p): (usize, &f32)| *p)
+            }
+        }
+    }
+
+    /// Sets the `p_value` of every anomaly with a z-score and flags it `significant` if it is at
+    /// most `cutoff`.
+    fn flag_at(anomalies: &mut [Anomaly], cutoff: f32) {
+        for info in anomalies.iter_mut() {
+            info.p_value = info.z_score.map(two_sided_p_value);
+            info.significant = info.p_value.map(|p: f32| p <= cutoff);
+        }
+    }
+}
+
+impl std::str::FromStr for Correction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bonferroni" => Ok(Correction::Bonferroni),
+            "bh" | "benjamini-hochberg" => Ok(Correction::BenjaminiHochberg),
+            _ => Err(format!("Unknown correction: {}", s)),
+        }
+    }
+}
+
+/// Returns the probability of a standard normal value at least as far from zero as `z`,
+/// `erfc(|z| / sqrt(2))`, with a relative error below `1.2e-7` (Numerical Recipes' `erfcc`).
+/// NaN for a NaN score.
+fn two_sided_p_value(z: f32) -> f32 {
+    let x: f64 = (z as f64).abs() / std::f64::consts::SQRT_2;
+    let t: f64 = 1.0 / (1.0 + 0.5 * x);
+    let polynomial: f64 = [0.17087277, -0.82215223, 1.48851587, -1.13520398, 0.27886807, -0.18628806, 0.09678418, 0.37409196, 1.00002368, -1.26551223]
+        .iter()
+        .fold(0.0, |sum: f64, coefficient: &f64| sum * t + coefficient);
+    (t * (-x * x + polynomial).exp()) as f32
+}
+
+/// Arrow type of the value columns: 32-bit integers, or with `bigint` 128-bit decimals
+/// without a fractional part, Arrow having no 128-bit integer type.
+#[cfg(all(feature = "arrow", not(feature = "bigint")))]
+type ValueArrowType = arrow_array::types::Int32Type;
+#[cfg(all(feature = "arrow", feature = "bigint"))]
+type ValueArrowType = arrow_array::types::Decimal128Type;
+#[cfg(feature = "arrow")]
+type ValueArray = arrow_array::PrimitiveArray<ValueArrowType>;
+
+#[cfg(feature = "arrow")]
+fn value_data_type() -> arrow_schema::DataType {
+    #[cfg(not(feature = "bigint"))]
+    let data_type = arrow_schema::DataType::Int32;
+    #[cfg(feature = "bigint")]
+    let data_type = arrow_schema::DataType::Decimal128(38, 0);
+    data_type
+}
+
+/// Number of decimals of the floats in canonical JSON output, see [`SearchConfig::canonical`].
+const CANONICAL_PRECISION: usize = 6;
+
+/// JSON formatter that writes the values of `centroid` keys as integers rounded with
+/// `rounding`, other floats with `precision` decimals, and leaves everything else, including
+/// the floats without either setting, to `inner`.
+struct FloatFormatter<F> {
+    inner: F,
+    rounding: Option<CentroidRounding>,
+    precision: Option<usize>,
+    /// Whether the string being written is an object key.
+    in_key: bool,
+    /// The most recent object key.
+    key: String,
+}
+
+impl<F> FloatFormatter<F> {
+    fn new(inner: F, rounding: Option<CentroidRounding>, precision: Option<usize>) -> Self {
+        FloatFormatter { inner, rounding, precision, in_key: false, key: String::new() }
+    }
+}
+
+impl<F: serde_json::ser::Formatter> serde_json::ser::Formatter for FloatFormatter<F> {
+    fn write_f32<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, value: f32) -> std::io::Result<()> {
+        match (self.rounding, self.precision) {
+            (Some(rounding), _) if self.key == "centroid" => self.inner.write_i64(writer, rounding.apply(value)),
+            (_, Some(_)) => self.write_f64(writer, value as f64),
+            _ => self.inner.write_f32(writer, value),
+        }
+    }
+
+    fn write_f64<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, value: f64) -> std::io::Result<()> {
+        match (self.rounding, self.precision) {
+            (Some(rounding), _) if self.key == "centroid" => self.inner.write_i64(writer, rounding.apply(value as f32)),
+            (_, Some(precision)) => {
+                let fixed: String = format!("{:.*}", precision, value);
+                // Values that round to zero are written without a sign, whichever side they are on.
+                let fixed: &str = match fixed.strip_prefix('-') {
+                    Some(unsigned) if unsigned.bytes().all(|byte: u8| matches!(byte, b'0' | b'.')) => unsigned,
+                    _ => &fixed,
+                };
+                writer.write_all(fixed.as_bytes())
+            }
+            _ => self.inner.write_f64(writer, value),
+        }
+    }
+
+    // With arbitrary precision, which the `bigint` feature enables, the numbers of a
+    // `serde_json::Value` arrive as their text instead, and those with a fraction or exponent
+    // are floats.
+    fn write_number_str<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, value: &str) -> std::io::Result<()> {
+        match value.parse::<f64>() {
+            Ok(float) if value.contains(['.', 'e', 'E']) => self.write_f64(writer, float),
+            _ => self.inner.write_number_str(writer, value),
+        }
+    }
+
+    fn write_string_fragment<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, fragment: &str) -> std::io::Result<()> {
+        if self.in_key {
+            self.key.push_str(fragment);
+        }
+        self.inner.write_string_fragment(writer, fragment)
+    }
+
+    fn begin_array<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.inner.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.inner.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, first: bool) -> std::io::Result<()> {
+        self.inner.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.inner.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.inner.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.inner.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, first: bool) -> std::io::Result<()> {
+        self.in_key = true;
+        self.key.clear();
+        self.inner.begin_object_key(writer, first)
+    }
+
+    fn end_object_key<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.in_key = false;
+        self.inner.end_object_key(writer)
+    }
+
+    fn begin_object_value<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.inner.begin_object_value(writer)
+    }
+
+    fn end_object_value<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
+        self.inner.end_object_value(writer)
+    }
+}
+
+/// Top-level object of the per-axis output of [`Lyagushka::search_axes`].
+#[derive(Serialize)]
+struct AxesReport {
+    x: Vec<Anomaly>,
+    y: Vec<Anomaly>,
+}
+
+#[pyclass]
+pub struct Lyagushka {
+    dataset: Vec<Value>,
+    anomalies: Vec<Anomaly>,
+    summary: DatasetSummary,
+    points: Option<Vec<(Value, Value)>>,
+    /// The distinct values of a weighted dataset with their frequencies, ordered by value.
+    frequencies: Option<Vec<(Value, usize)>>,
+    input_indices: Option<Vec<usize>>,
+    config: Option<SearchConfig>,
+    baseline: Baseline,
+    /// The most anomalies the last analysis held at once, which `max_clusters` and `max_gaps`
+    /// bound.
+    max_held: usize,
+}
+
+impl Default for Lyagushka {
+    fn default() -> Self {
+        Lyagushka::empty()
+    }
+}
+
+/// Strict parsing of text with one value per line, see [`Lyagushka::parse`].
+impl std::str::FromStr for Lyagushka {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lyagushka::parse(s, true)
+    }
+}
+
+/// Strict parsing of text with one value per line, see [`Lyagushka::parse`].
+impl TryFrom<&str> for Lyagushka {
+    type Error = Error;
+
+    fn try_from(text: &str) -> Result<Self, Self::Error> {
+        Lyagushka::parse(text, true)
+    }
+}
+
+#[pymethods]
+impl Lyagushka {
+    
+    /// Creates an instance analyzing `dataset`, in any order.
+    ///
+    /// Fails if the dataset is too wide to measure, see [`Lyagushka::check_range`].
+    #[new]
+    pub fn new(dataset: Vec<Value>) -> Result<Self, Error> {
+        let zhaba = Lyagushka::unchecked(dataset);
+        zhaba.check_range()?;
+        Ok(zhaba)
+    }
+
+    /// Creates an instance without any data; analyzing it finds no anomalies.
+    #[staticmethod]
+    pub fn empty() -> Self {
+        Lyagushka::unchecked(Vec::new())
+    }
+
+    /// Creates an instance whose dataset is the differences `series[i + 1] - series[i]` between
+    /// successive values of `series`, taken in the given order.
+    ///
+    /// Clusters are then ranges of step sizes that occur unusually often, and gaps ranges of
+    /// step sizes that don't occur; `start` and `end` are step sizes rather than positions,
+    /// and the input `indices` of a cluster's elements refer to the first of the two values.
+    ///
+    /// Fails if a difference does not fit into a [`Value`], naming the two successive values,
+    /// or if the differences are too wide to measure.
+    #[staticmethod]
+    pub fn from_deltas(series: Vec<Value>) -> Result<Self, Error> {
+        let deltas: Vec<Value> = series.windows(2)
+            .map(|w: &[Value]| w[1].checked_sub(w[0]).ok_or(Error::RangeOverflow { min: w[0], max: w[1] }))
+            .collect::<Result<Vec<Value>, Error>>()?;
+        Lyagushka::new(deltas)
+    }
+
+    /// Creates an instance from pre-binned data, pairs of a value and its frequency, the number
+    /// of points at the value. Frequencies of repeated values add up.
+    ///
+    /// The distinct values with a non-zero frequency make up the dataset, so the distances and
+    /// thresholds are those between them, and `min_cluster_size` counts distinct values. The
+    /// `num_elements` of a cluster is the total frequency of its elements instead, which is
+    /// what its density is measured by.
+    ///
+    /// Fails if the values are too wide to measure.
+    #[staticmethod]
+    pub fn from_frequencies(mut histogram: Vec<(Value, usize)>) -> Result<Self, Error> {
+        histogram.retain(|&(_, frequency): &(Value, usize)| frequency > 0);
+        histogram.sort_unstable_by_key(|&(value, _): &(Value, usize)| value);
+        let mut frequencies: Vec<(Value, usize)> = Vec::with_capacity(histogram.len());
+        for (value, frequency) in histogram {
+            match frequencies.last_mut() {
+                Some(last) if last.0 == value => last.1 += frequency,
+                _ => frequencies.push((value, frequency)),
+            }
+        }
+        let mut zhaba = Lyagushka::new(frequencies.iter().map(|&(value, _): &(Value, usize)| value).collect())?;
+        zhaba.frequencies = Some(frequencies);
+        Ok(zhaba)
+    }
+
+    /// Creates an instance from text with one value per line, with the default [`ParseOptions`]
+    /// of the command-line tool apart from `strict`. Surrounding whitespace, blank lines and
+    /// comment lines starting with `#` are ignored. Lines that are not a [`Value`], because they
+    /// are not an integer or out of range, are an error with `strict` and skipped otherwise, and
+    /// NaN or infinities are always an error.
+    ///
+    /// See [`Lyagushka::parse_with`] for the other options and [`parse_lines`] to learn which
+    /// lines were skipped.
+    #[staticmethod]
+    pub fn parse(text: &str, strict: bool) -> Result<Self, Error> {
+        Lyagushka::parse_with(text, &ParseOptions { strict, ..ParseOptions::default() })
+    }
+
+    /// Creates an instance whose anomaly storage is preallocated for `capacity` entries.
+    ///
+    /// Fails like [`Lyagushka::new`].
+    #[staticmethod]
+    pub fn with_capacity(dataset: Vec<Value>, capacity: usize) -> Result<Self, Error> {
+        let mut zhaba = Lyagushka::new(dataset)?;
+        zhaba.anomalies.reserve_exact(capacity);
+        Ok(zhaba)
+    }
+
+    /// Creates an instance from two-dimensional points, to be analyzed one axis at a time
+    /// with [`Lyagushka::search_projected`] or [`Lyagushka::search_axes`].
+    ///
+    /// Until then, the dataset is the projection onto the x axis.
+    ///
+    /// Fails if the projection onto either axis is too wide to measure.
+    #[staticmethod]
+    pub fn new_2d(points: Vec<(Value, Value)>) -> Result<Self, Error> {
+        Lyagushka::new(points.iter().map(|point: &(Value, Value)| point.1).collect())?;
+        let mut zhaba = Lyagushka::new(points.iter().map(|point: &(Value, Value)| point.0).collect())?;
+        zhaba.points = Some(points);
+        Ok(zhaba)
+    }
+
+    /// Iterates over the anomalies of the last analysis; yields nothing if no analysis has been run.
+    fn __iter__(&self) -> AnomalyIterator {
+        AnomalyIterator {
+            inner: self.anomalies.clone().into_iter(),
+        }
+    }
+
+    /// Checks that the dataset is in ascending order, failing with the index of the first value
+    /// that is smaller than its predecessor. Meant to run before the first analysis, which sorts
+    /// the dataset.
+    pub fn check_sorted(&self) -> Result<(), Error> {
+        match self.dataset.windows(2).position(|w: &[Value]| w[1] < w[0]) {
+            Some(position) => Err(Error::Unsorted { index: position + 1 }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the distance between the smallest and the largest value fits into a [`Value`],
+    /// which only fails for mixed-sign datasets spanning more than half the integer range. The
+    /// analysis measures every span as such a difference and would overflow on them, so the
+    /// constructors fail on such datasets.
+    pub fn check_range(&self) -> Result<(), Error> {
+        let (Some(&min), Some(&max)) = (self.dataset.iter().min(), self.dataset.iter().max()) else {
+            return Ok(());
+        };
+        match max.checked_sub(min) {
+            Some(_) => Ok(()),
+            None => Err(Error::RangeOverflow { min, max }),
+        }
+    }
+
+    /// Checks that no two anomalies of the last analysis overlap, failing with the positions of
+    /// the first overlapping pair found. Anomalies that only share an endpoint touch and pass.
+    pub fn check_disjoint(&self) -> Result<(), Error> {
+        let mut order: Vec<usize> = (0..self.anomalies.len()).collect();
+        order.sort_by_key(|&position: &usize| (self.anomalies[position].start, self.anomalies[position].end));
+
+        // Sorted by start and then by end, an anomaly overlaps an earlier one exactly if it
+        // overlaps the one reaching furthest.
+        let mut furthest: Option<usize> = None;
+        for position in order {
+            let info: &Anomaly = &self.anomalies[position];
+            match furthest {
+                Some(other) if info.start < self.anomalies[other].end && self.anomalies[other].start < info.end => {
+                    return Err(Error::Overlap { first: other.min(position), second: other.max(position) });
+                }
+                Some(other) if info.end <= self.anomalies[other].end => {}
+                _ => furthest = Some(position),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the statistics the last analysis scored against: the supplied baseline, or the
+    /// one derived from the dataset, which can be saved to score later data against.
+    pub fn baseline(&self) -> Baseline {
+        self.baseline
+    }
+
+    /// Builds an [`AnomalyIndex`] over the anomalies of the last analysis.
+    pub fn index(&self) -> AnomalyIndex {
+        AnomalyIndex::new(self.anomalies.clone())
+    }
+
+    /// The dataset as a list, in ascending order once an analysis has been run.
+    #[getter(dataset)]
+    fn py_dataset(&self) -> Vec<Value> {
+        self.dataset.clone()
+    }
+
+    /// Returns the number of points within `[lo, hi]` with two binary searches, which requires
+    /// the dataset to be sorted, as it is once an analysis has been run.
+    pub fn count_in_range(&self, lo: Value, hi: Value) -> usize {
+        self.points_in_range(lo, hi).len()
+    }
+
+    /// Python wrapper around [`Lyagushka::last_summary`].
+    #[pyo3(name = "last_summary")]
+    fn py_last_summary(&self) -> Option<DatasetSummary> {
+        self.last_summary().cloned()
+    }
+
+    /// Python wrapper around [`Lyagushka::points_in_range`].
+    #[pyo3(name = "points_in_range")]
+    fn py_points_in_range(&self, lo: Value, hi: Value) -> Vec<Value> {
+        self.points_in_range(lo, hi).to_vec()
+    }
+
+    pub fn search(&mut self, factor: f32, min_cluster_size: usize) -> String {
+        self.search_with(&SearchConfig::new(factor, min_cluster_size))
+    }
+
+    /// Identical to [`Lyagushka::search`], but takes the full set of options from a [`SearchConfig`].
+    pub fn search_with(&mut self, config: &SearchConfig) -> String {
+        self.analyze(config);
+        self.to_json()
+    }
+
+    /// Identical to [`Lyagushka::search_with`], but also returns the anomalies the JSON was
+    /// serialized from, for callers that store the one and inspect the other.
+    pub fn search_with_anomalies(&mut self, config: &SearchConfig) -> (String, Vec<Anomaly>) {
+        self.analyze(config);
+        (self.to_json(), self.anomalies.clone())
+    }
+
+    /// Projects the two-dimensional points onto `axis` and runs the one-dimensional analysis on the result.
+    ///
+    /// Fails if the instance was not created with [`Lyagushka::new_2d`].
+    pub fn search_projected(&mut self, axis: Axis, config: &SearchConfig) -> Result<String, Error> {
+        self.project(axis)?;
+        Ok(self.search_with(config))
+    }
+
+    /// Analyzes the projections onto both axes and returns the anomalies per axis as a
+    /// JSON object with the keys `x` and `y`.
+    ///
+    /// Fails if the instance was not created with [`Lyagushka::new_2d`].
+    pub fn search_axes(&mut self, config: &SearchConfig) -> Result<String, Error> {
+        self.project(Axis::X)?;
+        self.analyze(config);
+        let x: Vec<Anomaly> = std::mem::take(&mut self.anomalies);
+        self.project(Axis::Y)?;
+        self.analyze(config);
+        let report = AxesReport {
+            x,
+            y: self.anomalies.clone(),
+        };
+        Ok(self.serialize(&report))
+    }
+
+    /// Sorts the dataset and returns a JSON histogram of the distances between consecutive points
+    /// in `buckets` equally wide buckets, together with the thresholds `config` would apply.
+    pub fn threshold_report(&mut self, config: &SearchConfig, buckets: usize) -> String {
+        self.config = Some(config.clone());
+        self.dataset.sort_unstable();
+        let histogram: DistanceHistogram = self.distance_histogram(config, buckets);
+        self.serialize(&histogram)
+    }
+
+    /// Sorts the dataset and returns a JSON array of the points per bin between consecutive
+    /// `edges`, see [`Lyagushka::bin_counts`].
+    pub fn bin_report(&mut self, config: &SearchConfig, edges: Vec<Value>) -> String {
+        let bins: Vec<BinCount> = self.bin_counts(config, &edges);
+        self.serialize(&bins)
+    }
+
+    /// Runs the analysis and returns its clusters as a JSON object of coarse `groups` and
+    /// `ungrouped` fine clusters, see [`Lyagushka::two_scale_clusters`].
+    pub fn two_scale_report(&mut self, config: &SearchConfig, coarse_factor: f32) -> String {
+        let clusters: TwoScaleClusters = self.two_scale_clusters(config, coarse_factor);
+        self.serialize(&clusters)
+    }
+
+    /// Sorts the dataset and returns a JSON array of its density at `resolution` positions, see
+    /// [`Lyagushka::density_profile`].
+    pub fn profile_report(&mut self, config: &SearchConfig, resolution: usize) -> String {
+        self.config = Some(config.clone());
+        let profile: Vec<ProfilePoint> = self.density_profile(resolution);
+        self.serialize(&profile)
+    }
+
+    /// Sorts the dataset and returns the distances between consecutive points, the raw input
+    /// all thresholds are derived from. The result has one entry less than the dataset.
+    pub fn distances(&mut self) -> Vec<Value> {
+        self.dataset.sort_unstable();
+        self.dataset.windows(2).map(|w: &[Value]| w[1] - w[0]).collect()
+    }
+
+    /// Runs the analysis and returns only the anomaly with the largest absolute z-score,
+    /// or `None` if no anomalies were found.
+    pub fn most_significant(&mut self, factor: f32, min_cluster_size: usize) -> Option<Anomaly> {
+        self.analyze(&SearchConfig::new(factor, min_cluster_size));
+        self.anomalies.iter()
+            .filter_map(|info: &Anomaly| info.z_score.filter(|z: &f32| !z.is_nan()).map(|z: f32| (info, z.abs())))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(info, _)| info.clone())
+    }
+
+    /// Runs the analysis and returns the anomalies column-wise, as a dict mapping each field to
+    /// a list with one entry per anomaly, ready for `pandas.DataFrame`. Missing z-scores are `None`.
+    pub fn to_columns<'py>(&mut self, py: Python<'py>, factor: f32, min_cluster_size: usize) -> PyResult<Bound<'py, PyDict>> {
+        self.analyze(&SearchConfig::new(factor, min_cluster_size));
+        let anomalies: &[Anomaly] = &self.anomalies;
+        let columns = PyDict::new(py);
+        columns.set_item("elements", anomalies.iter().map(|info: &Anomaly| info.elements.clone()).collect::<Vec<Vec<Value>>>())?;
+        columns.set_item("start", anomalies.iter().map(|info: &Anomaly| info.start).collect::<Vec<Value>>())?;
+        columns.set_item("end", anomalies.iter().map(|info: &Anomaly| info.end).collect::<Vec<Value>>())?;
+        columns.set_item("span_length", anomalies.iter().map(|info: &Anomaly| info.span_length).collect::<Vec<Value>>())?;
+        columns.set_item("num_elements", anomalies.iter().map(|info: &Anomaly| info.num_elements).collect::<Vec<usize>>())?;
+        columns.set_item("centroid", anomalies.iter().map(|info: &Anomaly| info.centroid).collect::<Vec<f32>>())?;
+        columns.set_item("z_score", anomalies.iter().map(|info: &Anomaly| info.z_score).collect::<Vec<Option<f32>>>())?;
+        columns.set_item("dead_zone", anomalies.iter().map(|info: &Anomaly| info.dead_zone).collect::<Vec<bool>>())?;
+        columns.set_item("edge", anomalies.iter().map(|info: &Anomaly| info.edge).collect::<Vec<bool>>())?;
+        Ok(columns)
+    }
+
+    /// Runs the analysis and returns the anomalies as a FeatureCollection, see [`Lyagushka::to_features`].
+    pub fn search_features(&mut self, config: &SearchConfig) -> String {
+        self.analyze(config);
+        self.to_features()
+    }
+
+    /// Runs the analysis and returns the anomalies together with the dataset summary, see [`Lyagushka::to_report`].
+    pub fn search_report(&mut self, config: &SearchConfig) -> String {
+        self.analyze(config);
+        self.to_report()
+    }
+
+    /// Serializes the anomalies of the last analysis as a JSON array.
+    pub fn to_json(&self) -> String {
+        self.serialize(&self.anomalies)
+    }
+
+    /// Serializes the last analysis as a JSON object with a `summary` of the dataset-level
+    /// statistics and metadata next to the `anomalies` array.
+    pub fn to_report(&self) -> String {
+        let report = Report {
+            summary: &self.summary,
+            anomalies: &self.anomalies,
+        };
+        self.serialize(&report)
+    }
+
+    /// Serializes the last analysis as a self-contained JSON object holding the sorted `dataset`,
+    /// the `parameters` it was analyzed with and the resulting `anomalies`.
+    pub fn to_bundle(&self) -> String {
+        let bundle = Bundle {
+            algorithm_version: ALGORITHM_VERSION,
+            dataset: &self.dataset,
+            parameters: self.config.as_ref(),
+            anomalies: &self.anomalies,
+        };
+        self.serialize(&bundle)
+    }
+
+    /// Serializes the start and end positions of the anomalies of the last analysis as a flat
+    /// JSON array ordered by position, see [`Lyagushka::boundaries`].
+    pub fn to_boundaries(&self) -> String {
+        self.serialize(&self.boundaries())
+    }
+
+    /// Serializes the anomalies of the last analysis as a GeoJSON-like FeatureCollection.
+    ///
+    /// Each anomaly becomes a `Feature` whose `properties` hold the anomaly's fields and whose
+    /// `range` is the `[start, end]` interval it covers.
+    pub fn to_features(&self) -> String {
+        let collection = FeatureCollection {
+            kind: "FeatureCollection",
+            features: self.anomalies.iter()
+                .map(|info: &Anomaly| Feature {
+                    kind: "Feature",
+                    properties: info,
+                    range: [info.start, info.end],
+                })
+                .collect(),
+        };
+        self.serialize(&collection)
+    }
+
+    /// Sorts the dataset, detects clusters and gaps, and calculates their z-scores.
+    pub fn analyze(&mut self, config: &SearchConfig) {
+        self.config = Some(config.clone());
+        let started: Instant = Instant::now();
+
+        // Sort the vector, unless the caller vouches for the order. If the input order is to be
+        // restored, the input position of every value is captured during the first such
+        // analysis, before the dataset is reordered.
+        debug_assert!(!config.assume_sorted || self.check_sorted().is_ok(), "assume_sorted set for an unsorted dataset");
+        if config.input_order && self.input_indices.is_none() && config.assume_sorted {
+            self.input_indices = Some((0..self.dataset.len()).collect());
+        } else if config.input_order && self.input_indices.is_none() {
+            let mut order: Vec<usize> = (0..self.dataset.len()).collect();
+            order.sort_by_key(|&index: &usize| self.dataset[index]);
+            self.dataset = order.iter().map(|&index: &usize| self.dataset[index]).collect();
+            self.input_indices = Some(order);
+        } else if !config.assume_sorted {
+            self.dataset.sort_unstable();
+        }
+        debug!("Sorted {} points in {:?}", self.dataset.len(), started.elapsed());
+
+        // The values within the range of interest, or a sample of them, with coincident values
+        // merged, take the place of the dataset until the analysis is done.
+        let mut full_dataset: Option<(Vec<Value>, Option<Vec<usize>>)> = config.range.map(|(lo, hi): (Value, Value)| self.restrict(lo, hi));
+        let fraction: f32 = config.sample.filter(|fraction: &f32| *fraction > 0.0 && *fraction < 1.0).unwrap_or(1.0);
+        if fraction < 1.0 {
+            let dataset: (Vec<Value>, Option<Vec<usize>>) = self.sample(fraction, config.seed);
+            full_dataset.get_or_insert(dataset);
+        }
+        if let Some(epsilon) = config.epsilon.filter(|&epsilon: &Value| epsilon > 0 && self.frequencies.is_none()) {
+            let snapped: Vec<Value> = snap_coincident(&self.dataset, epsilon);
+            let dataset: Vec<Value> = std::mem::replace(&mut self.dataset, snapped);
+            full_dataset.get_or_insert((dataset, self.input_indices.clone()));
+        }
+    
+        // The cluster densities and spans, the spans of all anomalies and the values gaps are
+        // standardized by. The optional smoothing term keeps clusters with
+        // a tiny span from getting a runaway density. A cluster of zero span without smoothing
+        // has an infinite density, which is left out of the statistics and not scored, so that
+        // it doesn't swamp the scores of the others. In log-density mode clusters are scored by
+        // the logarithm of their density instead.
+        //
+        // In log-span and unified mode gaps are standardized against the other gaps only,
+        // using either the logarithm of their span or the raw span. Non-positive spans have
+        // no logarithm and are left out of the statistics. Dead zones and edge gaps are scored
+        // like gaps but never contribute to the statistics.
+        let smoothing: f32 = config.density_smoothing.unwrap_or(0.0);
+        let density = |info: &Anomaly| -> f32 { info.num_elements as f32 / (info.span_length as f32 + smoothing) };
+        let density_value = |info: &Anomaly| -> Option<f32> {
+            let density: f32 = density(info);
+            if !density.is_finite() {
+                None
+            } else if !config.log_densities {
+                Some(density)
+            } else if density > 0.0 {
+                Some(density.ln())
+            } else {
+                None
+            }
+        };
+        let gap_value = |info: &Anomaly| -> Option<f32> {
+            if !config.log_gap_spans {
+                Some(info.span_length as f32)
+            } else if info.span_length > 0 {
+                Some((info.span_length as f32).ln())
+            } else {
+                None
+            }
+        };
+        // The points of a weighted cluster count as often as they occur, and those of a sampled
+        // one are scaled back up to the full dataset.
+        let frequencies: Option<&Vec<(Value, usize)>> = self.frequencies.as_ref();
+        let weigh = |info: &mut Anomaly| {
+            if info.kind() != AnomalyKind::Cluster {
+                return;
+            }
+            if let Some(frequencies) = frequencies {
+                let frequency = |value: &Value| -> usize {
+                    frequencies.binary_search_by_key(value, |&(value, _): &(Value, usize)| value)
+                        .map_or(0, |position: usize| frequencies[position].1)
+                };
+                info.num_elements = info.elements.iter().map(frequency).sum();
+            }
+            if fraction < 1.0 {
+                info.num_elements = (info.num_elements as f32 / fraction).round() as usize;
+            }
+        };
+
+        // The statistics are gathered as the scan emits the anomalies. With mean-based scores on
+        // sorted input, or under a cap on the anomalies held, they are accumulated without storing
+        // the values, fusing the two passes; the other methods need all values at once. The scan
+        // emits the anomalies that contribute to them in their final order, so the results are
+        // identical either way.
+        let method: ScoreMethod = config.score_method;
+        let capped: bool = config.max_clusters.is_some() || config.max_gaps.is_some();
+        let fused: bool = method == ScoreMethod::Classic && (config.assume_sorted || capped);
+        let mut running: [RunningStatistics; 4] = [RunningStatistics::new(); 4];
+        let mut values: [Vec<f32>; 4] = Default::default();
+        let (mut num_anomalies, mut num_dead_zones): (usize, usize) = (0, 0);
+        let mut centroid_spacings: RunningStatistics = RunningStatistics::new();
+        let mut last_centroid: Option<f32> = None;
+        let mut observe = |info: &Anomaly| {
+            let mut push = |statistic: usize, value: f32| if fused { running[statistic].push(value) } else { values[statistic].push(value) };
+            num_anomalies += 1;
+            num_dead_zones += usize::from(info.dead_zone);
+            if info.kind() == AnomalyKind::Cluster {
+                density_value(info).into_iter().for_each(|value: f32| push(0, value));
+                push(1, info.span_length as f32);
+                if let Some(last) = last_centroid.replace(info.centroid) {
+                    centroid_spacings.push(info.centroid - last);
+                }
+            }
+            if info.in_statistics() {
+                push(2, info.span_length as f32);
+                if info.kind() == AnomalyKind::Gap {
+                    gap_value(info).into_iter().for_each(|value: f32| push(3, value));
+                }
+            }
+        };
+
+        // Calculate clusters and gaps from the dataset using predefined criteria. Under a cap,
+        // this first scan only gathers the statistics, and a second one scores the anomalies
+        // and keeps the most extreme.
+        let scan_started: Instant = Instant::now();
+        let mut anomalies: Vec<Anomaly> = Vec::new();
+        let summary: DatasetSummary = if capped {
+            self.scan_with(config, |mut info: Anomaly| {
+                weigh(&mut info);
+                observe(&info);
+            })
+        } else {
+            // Reserve room for roughly one cluster and one gap per `2 * min_cluster_size` points,
+            // so large runs don't reallocate repeatedly.
+            anomalies = std::mem::take(&mut self.anomalies);
+            anomalies.clear();
+            anomalies.reserve(self.dataset.len() / (2 * config.min_cluster_size.max(1)));
+            self.scan_with(config, |mut info: Anomaly| {
+                weigh(&mut info);
+                observe(&info);
+                anomalies.push(info);
+            })
+        };
+        self.summary = summary;
+        debug!("Scanned in {:?}", scan_started.elapsed());
+        if config.periodicity {
+            self.summary.detected_period = self.detect_period();
+        }
+
+        // Order the anomalies by position, breaking ties deterministically.
+        anomalies.sort_by_key(Anomaly::sort_key);
+        let zero_range: bool = self.is_zero_range();
+
+        // Calculate the location and scale of each to evaluate variation.
+        let [(mean_density, std_dev_density), (mean_cluster_span, std_dev_cluster_span), (mean_span_length, std_dev_span_length), (mean_gap_value, std_dev_gap_value)]: [(f32, f32); 4] =
+            if fused {
+                running.map(|statistics: RunningStatistics| {
+                    let (mean, variance) = statistics.mean_and_variance();
+                    (mean, variance.sqrt())
+                })
+            } else {
+                values.each_ref().map(|values: &Vec<f32>| method.location_and_scale(values))
+            };
+        let [densities, cluster_spans, span_lengths, gap_values] = &values;
+        let num_clusters: usize = if fused { running[1].count() } else { cluster_spans.len() };
+
+        // A supplied baseline takes the place of the statistics of the current dataset.
+        let gap_statistics: bool = config.unified_score || config.log_gap_spans;
+        let baseline: Baseline = config.baseline.unwrap_or(Baseline {
+            mean_density,
+            std_dev_density,
+            mean_span: if gap_statistics { mean_gap_value } else { mean_span_length },
+            std_dev_span: if gap_statistics { std_dev_gap_value } else { std_dev_span_length },
+        });
+        self.baseline = baseline;
+        let (mean_density, std_dev_density) = (baseline.mean_density, baseline.std_dev_density);
+        let (mean_gap_value, std_dev_gap_value) = (baseline.mean_span, baseline.std_dev_span);
+        let (mean_span_length, std_dev_span_length) = (baseline.mean_span, baseline.std_dev_span);
+        // The classic gap score is a ratio of the span to the spread of all anomaly spans rather
+        // than a deviation, which standardizing against a location of zero yields. The robust
+        // methods center the span on the median anomaly span, since their scale is meaningless
+        // without it.
+        let span_location: f32 = if method == ScoreMethod::Classic { 0.0 } else { mean_span_length };
+
+        // With shrinkage, the density of a cluster of `n` points is pulled towards the center of
+        // the densities by a fraction `1 / sqrt(n)` of its deviation before it is scored, so that
+        // small clusters, whose densities are noisy, need a more extreme one to stand out.
+        let shrink = |info: &Anomaly, value: f32| -> f32 {
+            if config.shrinkage && value.is_finite() {
+                mean_density + (value - mean_density) * (1.0 - 1.0 / (info.num_elements as f32).sqrt())
+            } else {
+                value
+            }
+        };
+    
+        // Update Z-scores for both clusters and gaps based on their deviation from mean metrics.
+        // Without any clusters or without any gaps, the statistics of that type are undefined,
+        // but then there is nothing of that type to score either. A single cluster or a set of
+        // equally dense ones has a zero standard deviation and is scored as average.
+        // Outside unified mode, gap z-scores are negated unless positive ones were requested.
+        let gap_sign: f32 = if config.positive_gap_scores { 1.0 } else { -1.0 };
+        let score = |info: &mut Anomaly| {
+            if info.kind() == AnomalyKind::Cluster {
+                // Calculate and update Z-score for clusters based on density deviation.
+                // The single cluster of a zero-range dataset has an infinite density and nothing to
+                // be compared with, so it is scored as exactly average.
+                let cluster_density: Option<f32> = density_value(info).map(|value: f32| shrink(info, value));
+                let z_score: Option<f32> = if zero_range {
+                    Some(method.average())
+                } else {
+                    cluster_density.and_then(|value: f32| method.score(value, densities, mean_density, std_dev_density))
+                };
+                // Under rank scoring, this compares the percentile of the density.
+                info.weak = config.weak_cluster_threshold.zip(z_score).is_some_and(|(threshold, z): (f32, f32)| z < threshold);
+                // Optionally blend in how unusual the cluster's span is among the clusters.
+                info.z_score = match config.density_weight {
+                    Some(weight) if !zero_range => z_score.zip(method.score(info.span_length as f32, cluster_spans, mean_cluster_span, std_dev_cluster_span))
+                        .map(|(z, span_z_score): (f32, f32)| weight * z + (1.0 - weight) * span_z_score),
+                    _ => z_score,
+                };
+                info.spacing_deviation = config.expected_spacing.zip(info.internal_gap_mean)
+                    .map(|(expected, mean): (f32, f32)| (mean - expected) / expected);
+            } else if config.unified_score || method == ScoreMethod::Rank {
+                // Standardize gaps like clusters, so that larger-than-usual gaps score positive.
+                // Ranks are always taken among the gaps, and a negated rank would mean nothing.
+                info.z_score = gap_value(info).and_then(|value: f32| method.score(value, gap_values, mean_gap_value, std_dev_gap_value));
+            } else if config.log_gap_spans {
+                // Calculate and update Z-score for gaps based on log-span deviation.
+                info.z_score = gap_value(info).and_then(|value: f32| method.score(value, gap_values, mean_gap_value, std_dev_gap_value))
+                    .map(|z: f32| gap_sign * z);
+            } else {
+                // Calculate and update Z-score for gaps based on span length deviation among all
+                // anomaly spans. Without any spread there is no score.
+                info.z_score = Some(std_dev_span_length).filter(|scale: &f32| *scale > 0.0)
+                    .and_then(|scale: f32| method.score(info.span_length as f32, span_lengths, span_location, scale))
+                    .map(|z: f32| gap_sign * z);
+            }
+
+            // Describe which statistics the z-score was derived from.
+            if config.explain {
+                let direction = |deviation: f32| if deviation >= 0.0 { "above" } else { "below" };
+                let weight: f32 = config.density_weight.unwrap_or(1.0);
+                let density_kind: &str = if config.log_densities { "log-density" } else { "density" };
+                let cluster_value: f32 = density_value(info).unwrap_or(f32::NAN);
+                let cluster_text: String = if config.shrinkage {
+                    format!("{} {:.3}, shrunk to {:.3} for its {} points,", density_kind, cluster_value, shrink(info, cluster_value), info.num_elements)
+                } else {
+                    format!("{} {:.3}", density_kind, cluster_value)
+                };
+                let (center, spread): (&str, &str) = match method {
+                    ScoreMethod::Robust | ScoreMethod::Modified => ("median", "scaled absolute deviation"),
+                    ScoreMethod::Classic | ScoreMethod::Rank => ("mean", "standard deviation"),
+                };
+                let explanation: Option<String> = info.z_score.map(|z: f32| if zero_range {
+                    format!("All {} values are identical, so they form a single zero-span cluster scored as average (z = {:.2})",
+                        info.elements.len(), z)
+                } else if method == ScoreMethod::Rank && (info.kind() == AnomalyKind::Gap || config.density_weight.is_none()) {
+                    let (kind, value): (&str, f32) = if info.kind() == AnomalyKind::Cluster {
+                        (if config.log_densities { "cluster log-density" } else { "cluster density" }, shrink(info, cluster_value))
+                    } else if config.log_gap_spans {
+                        ("gap log-span", (info.span_length as f32).ln())
+                    } else {
+                        ("gap span", info.span_length as f32)
+                    };
+                    format!("The {} {:.3} ranks at {:.0}% of the {} values (score = {:.2})", kind, value, z * 100.0, kind, z)
+                } else if info.kind() == AnomalyKind::Cluster && config.density_weight.is_some() {
+                    format!("Cluster {} against the {} of {:.3} and span {} against the {} of {:.1}, weighted {:.2} to {:.2} (z = {:.2})",
+                        cluster_text, center, mean_density, info.span_length, center, mean_cluster_span,
+                        weight, 1.0 - weight, z)
+                } else if info.kind() == AnomalyKind::Cluster {
+                    format!("Cluster {} is {:.2}σ {} the {} cluster {} of {:.3} (z = {:.2})",
+                        cluster_text, z.abs(), direction(z), center, density_kind, mean_density, z)
+                } else if config.unified_score || config.log_gap_spans {
+                    // Outside unified mode the gap z-score may be negated, so undo that for the direction.
+                    let deviation: f32 = if config.unified_score { z } else { gap_sign * z };
+                    if config.log_gap_spans {
+                        format!("Gap log-span {:.3} (span {}) is {:.2}σ {} the {} gap log-span of {:.3} (z = {:.2})",
+                            (info.span_length as f32).ln(), info.span_length, deviation.abs(), direction(deviation), center, mean_gap_value, z)
+                    } else {
+                        format!("Gap of {} is {:.2}σ {} the {} gap span of {:.1} (z = {:.2})",
+                            info.span_length, deviation.abs(), direction(deviation), center, mean_gap_value, z)
+                    }
+                } else if method == ScoreMethod::Classic {
+                    format!("Gap of {} spans {:.2}σ of the {} {:.1} of all anomaly spans (z = {:.2})",
+                        info.span_length, z.abs(), spread, std_dev_span_length, z)
+                } else {
+                    let deviation: f32 = gap_sign * z;
+                    format!("Gap of {} is {:.2}σ {} the {} anomaly span of {:.1} (z = {:.2})",
+                        info.span_length, deviation.abs(), direction(deviation), center, mean_span_length, z)
+                });
+                info.explanation = explanation;
+            }
+            if method == ScoreMethod::Rank {
+                info.rank_score = info.z_score.take();
+            }
+        };
+
+        // Clusters in regions expected to be dense count towards the statistics but are not
+        // reported.
+        let expected = |info: &Anomaly| -> bool {
+            info.kind() == AnomalyKind::Cluster && config.expected_dense.iter().any(|&(lo, hi): &(Value, Value)| lo <= info.start && info.end <= hi)
+        };
+        // The most significant p-value under the correction, from the p-values of all tests when
+        // not every tested anomaly is kept.
+        let mut cutoff: Option<f32> = None;
+        if capped {
+            let average: f32 = method.average();
+            let mut clusters: MostExtreme = MostExtreme::new(config.max_clusters, average);
+            let mut gaps: MostExtreme = MostExtreme::new(config.max_gaps, average);
+            let (mut tests, mut p_values): (usize, Vec<f32>) = (0, Vec::new());
+            let mut held: usize = 0;
+            self.scan_with(config, |mut info: Anomaly| {
+                weigh(&mut info);
+                score(&mut info);
+                if expected(&info) {
+                    return;
+                }
+                if let Some(z) = info.z_score.filter(|_| config.correction.is_some()) {
+                    tests += 1;
+                    if config.correction == Some(Correction::BenjaminiHochberg) {
+                        p_values.push(two_sided_p_value(z));
+                    }
+                }
+                match info.kind() {
+                    AnomalyKind::Cluster => clusters.push(info),
+                    AnomalyKind::Gap => gaps.push(info),
+                }
+                held = held.max(clusters.len() + gaps.len());
+            });
+            self.summary.truncated = clusters.dropped || gaps.dropped;
+            self.max_held = held;
+            cutoff = config.correction.map(|correction: Correction| correction.cutoff(config.alpha, tests, &mut p_values));
+            anomalies = clusters.into_anomalies().chain(gaps.into_anomalies()).collect();
+            anomalies.sort_by_key(Anomaly::sort_key);
+            if config.gap_centers {
+                // The kept gaps may lie next to clusters that were dropped.
+                let mut centers: GapCenters = GapCenters::new(&mut anomalies);
+                self.scan_with(config, |info: Anomaly| if info.kind() == AnomalyKind::Cluster {
+                    centers.cluster(info.start, info.end);
+                });
+                centers.finish();
+            }
+        } else {
+            self.max_held = anomalies.len();
+            anomalies.iter_mut().for_each(score);
+            if config.gap_centers {
+                let clusters: Vec<(Value, Value)> = anomalies.iter()
+                    .filter(|info: &&Anomaly| info.kind() == AnomalyKind::Cluster)
+                    .map(|info: &Anomaly| (info.start, info.end))
+                    .collect();
+                let mut centers: GapCenters = GapCenters::new(&mut anomalies);
+                clusters.into_iter().for_each(|(start, end): (Value, Value)| centers.cluster(start, end));
+                centers.finish();
+            }
+            anomalies.retain(|info: &Anomaly| !expected(info));
+        }
+        self.anomalies = anomalies;
+
+        self.summary.num_clusters = num_clusters;
+        self.summary.num_dead_zones = num_dead_zones;
+        self.summary.num_gaps = num_anomalies - num_clusters - num_dead_zones;
+        if centroid_spacings.count() > 0 {
+            let (mean, variance) = centroid_spacings.mean_and_variance();
+            self.summary.mean_inter_cluster_gap = Some(mean);
+            self.summary.std_dev_inter_cluster_gap = Some(variance.sqrt());
+        }
+
+        if let Some(correction) = config.correction {
+            match cutoff {
+                Some(cutoff) => Correction::flag_at(&mut self.anomalies, cutoff),
+                None => correction.flag_significant(&mut self.anomalies, config.alpha),
+            }
+        }
+
+        if config.rank {
+            // The anomalies are in ascending order, which the stable sort keeps for equal scores.
+            // Under a cap, every anomaly more extreme than a kept one is kept too, so the ranks
+            // among the kept anomalies are those among all of them.
+            let average: f32 = config.score_method.average();
+            let kind = |info: &Anomaly| match info.kind() {
+                AnomalyKind::Cluster => 0,
+                AnomalyKind::Gap if info.dead_zone => 2,
+                AnomalyKind::Gap => 1,
+            };
+            let mut order: Vec<(usize, f32)> = self.anomalies.iter().enumerate()
+                .filter_map(|(position, info): (usize, &Anomaly)| info.extremity(average).map(|extremity: f32| (position, extremity)))
+                .collect();
+            order.sort_by(|a: &(usize, f32), b: &(usize, f32)| b.1.total_cmp(&a.1));
+            let mut ranks: [usize; 3] = [0; 3];
+            for (position, _) in order {
+                let info: &mut Anomaly = &mut self.anomalies[position];
+                ranks[kind(info)] += 1;
+                info.rank = Some(ranks[kind(info)]);
+            }
+        }
+
+        if let Some(band) = config.stability_band {
+            self.score_stability(config, band, started);
+        }
+
+        if let Some(max) = config.clamp_z.map(f32::abs).filter(|max: &f32| !max.is_nan()) {
+            for info in self.anomalies.iter_mut() {
+                info.z_score = info.z_score.map(|z: f32| z.clamp(-max, max));
+            }
+        }
+
+        if config.core_points {
+            let threshold: f32 = self.summary.cluster_threshold;
+            for info in self.anomalies.iter_mut().filter(|info: &&mut Anomaly| info.kind() == AnomalyKind::Cluster) {
+                let core_count: usize = count_core_points(&info.elements, threshold, config.inclusive_cluster_threshold, config.min_cluster_size);
+                info.core_count = Some(core_count);
+                info.border_count = Some(info.elements.len() - core_count);
+            }
+        }
+
+        if config.gap_interiors {
+            for info in self.anomalies.iter_mut().filter(|info: &&mut Anomaly| info.kind() == AnomalyKind::Gap && info.span_length >= 2) {
+                info.empty_start = Some(info.start + 1);
+                info.empty_end = Some(info.end - 1);
+            }
+        }
+
+        if let Some(unit) = config.time_unit {
+            for info in self.anomalies.iter_mut() {
+                info.duration = Some(unit.format_duration(info.span_length));
+            }
+        }
+
+        if let Some(max_elements) = config.max_elements {
+            for info in self.anomalies.iter_mut().filter(|info: &&mut Anomaly| info.num_elements > max_elements) {
+                info.elements = Vec::new();
+                info.indices = info.indices.as_ref().map(|_| Vec::new());
+                info.elements_truncated = true;
+            }
+        }
+
+        if let Some((dataset, input_indices)) = full_dataset {
+            self.dataset = dataset;
+            self.input_indices = input_indices;
+        }
+        debug_assert!(self.check_disjoint().is_ok(), "analysis produced overlapping anomalies");
+        debug!("Analysis finished in {:?}: {} clusters, {} gaps, {} dead zones, holding at most {} anomalies",
+            started.elapsed(), self.summary.num_clusters, self.summary.num_gaps, self.summary.num_dead_zones, self.max_held);
+    }
+}
+
+impl Lyagushka {
+
+    /// Creates an instance from text with one value per line, read with `options` by
+    /// [`parse_lines`]. Weighted input creates the instance [`Lyagushka::from_frequencies`].
+    pub fn parse_with(text: &str, options: &ParseOptions) -> Result<Self, Error> {
+        let input: ParsedInput = parse_lines(text.lines(), options)?;
+        if options.weighted {
+            Lyagushka::from_frequencies(input.values)
+        } else {
+            Lyagushka::new(input.values.into_iter().map(|(value, _): (Value, usize)| value).collect())
+        }
+    }
+
+    /// Creates an instance without checking the range of `dataset`, which must be known to be
+    /// measurable.
+    fn unchecked(dataset: Vec<Value>) -> Self {
+        Lyagushka {
+            dataset,
+            anomalies: vec![],
+            summary: DatasetSummary::default(),
+            points: None,
+            frequencies: None,
+            input_indices: None,
+            config: None,
+            baseline: Baseline::default(),
+            max_held: 0,
+        }
+    }
+
+    /// Returns the dataset, sorted in ascending order once an analysis has been run.
+    pub fn dataset(&self) -> &[Value] {
+        &self.dataset
+    }
+
+    /// Returns the points within `[lo, hi]`, empty if `lo > hi`, see [`Lyagushka::count_in_range`].
+    pub fn points_in_range(&self, lo: Value, hi: Value) -> &[Value] {
+        let from: usize = self.dataset.partition_point(|&value: &Value| value < lo);
+        let to: usize = self.dataset.partition_point(|&value: &Value| value <= hi).max(from);
+        &self.dataset[from..to]
+    }
+
+    /// Scans the dataset for anomalies with the thresholds of `config`, passing each to `emit`
+    /// as it is found instead of holding on to them. Returns the summary of the scan, with its
+    /// thresholds and the number of discarded candidate clusters.
+    fn scan_with<E>(&self, config: &SearchConfig, mut emit: E) -> DatasetSummary
+    where
+        E: FnMut(Anomaly),
+    {
+        let thresholds: Thresholds = self.thresholds(config);
+        let Thresholds { mean_distance, cluster_threshold, cluster_exit_threshold, gap_threshold } = thresholds;
+
+        let mut summary: DatasetSummary = DatasetSummary {
+            algorithm_version: ALGORITHM_VERSION,
+            units: config.units.clone(),
+            num_points: self.dataset.len(),
+            mean_distance,
+            cluster_threshold,
+            cluster_exit_threshold,
+            gap_threshold,
+            ..DatasetSummary::default()
+        };
+        debug!("Scanning {} points: mean distance {}, cluster threshold {} (exit {}), gap threshold {}",
+            self.dataset.len(), mean_distance, cluster_threshold, cluster_exit_threshold, gap_threshold);
+
+        // Pass on the findings of the scan as anomalies, re-emitting clusters in input order if requested.
+        let options = ScanOptions {
+            thresholds,
+            min_cluster_size: config.min_cluster_size,
+            inclusive_cluster_threshold: config.inclusive_cluster_threshold,
+            inclusive_gap_threshold: config.inclusive_gap_threshold,
+            report_dead_zones: config.report_dead_zones,
+            edge_gaps: config.edge_gaps,
+        };
+        let dataset: &[Value] = &self.dataset;
+        let input_indices: Option<&[usize]> = if config.input_order { self.input_indices.as_deref() } else { None };
+        let mut found: usize = 0;
+        let mut push = |info: Anomaly| {
+            found += 1;
+            emit(info);
+        };
+        lyagushka_core::scan(dataset, &options, |start: Value, end: Value| config.is_masked(start, end), |segment: Segment| match segment {
+            Segment::Cluster { first, last } => {
+                trace!("Cluster of {} points from {} to {}", last - first + 1, dataset[first], dataset[last]);
+                push(Anomaly::from_sorted(&dataset[first..=last], first, input_indices));
+            }
+            Segment::Discarded { .. } => summary.num_discarded_clusters += 1,
+            Segment::Gap { position } => {
+                trace!("Gap from {} to {}", dataset[position], dataset[position + 1]);
+                push(Anomaly::gap(dataset[position], dataset[position + 1]));
+            }
+            Segment::DeadZone { position } => {
+                let mut dead_zone: Anomaly = Anomaly::gap(dataset[position], dataset[position + 1]);
+                dead_zone.dead_zone = true;
+                push(dead_zone);
+            }
+            Segment::EdgeGap { start, end } => {
+                let mut edge_gap: Anomaly = Anomaly::gap(start, end);
+                edge_gap.edge = true;
+                push(edge_gap);
+            }
+        });
+
+        debug!("Scan found {} anomalies and discarded {} candidate clusters", found, summary.num_discarded_clusters);
+        summary
+    }
+
+    /// Consumes the instance and returns the dataset, see [`Lyagushka::dataset`].
+    pub fn into_dataset(self) -> Vec<Value> {
+        self.dataset
+    }
+
+    /// Returns the anomalies of the last analysis, ordered by `start`.
+    pub fn anomalies(&self) -> &[Anomaly] {
+        &self.anomalies
+    }
+
+    /// Returns the positions where the anomalies of the last analysis begin and end, ordered by
+    /// position. Where one anomaly ends at the point the next one starts, the end comes first.
+    pub fn boundaries(&self) -> Vec<Boundary> {
+        let mut boundaries: Vec<Boundary> = self.anomalies.iter()
+            .flat_map(|info: &Anomaly| {
+                let (start, end) = match info.kind() {
+                    AnomalyKind::Cluster => (BoundaryKind::ClusterStart, BoundaryKind::ClusterEnd),
+                    AnomalyKind::Gap => (BoundaryKind::GapStart, BoundaryKind::GapEnd),
+                };
+                [Boundary { position: info.start, kind: start }, Boundary { position: info.end, kind: end }]
+            })
+            .collect();
+        boundaries.sort_by_key(|boundary: &Boundary| boundary.position);
+        boundaries
+    }
+
+    /// Runs the analysis and detects clusters of its clusters, for data with tight groups that
+    /// themselves form looser super-groups.
+    ///
+    /// The fine clusters are those `config` finds. Their centroids, rounded to the nearest value,
+    /// are then analyzed as a dataset of their own with `coarse_factor`, usually smaller than
+    /// `config.factor` since the super-groups are looser, a minimum cluster size of two and the
+    /// configured [`ScoreMethod`]. Every coarse cluster found among the centroids becomes a
+    /// [`ClusterGroup`] of the fine clusters it contains. The anomalies of the instance are those
+    /// of the fine analysis.
+    pub fn two_scale_clusters(&mut self, config: &SearchConfig, coarse_factor: f32) -> TwoScaleClusters {
+        self.analyze(config);
+        let clusters: Vec<Anomaly> = self.anomalies.iter()
+            .filter(|info: &&Anomaly| info.kind() == AnomalyKind::Cluster)
+            .cloned()
+            .collect();
+        let centroids: Vec<Value> = clusters.iter().map(|info: &Anomaly| info.centroid.round() as Value).collect();
+
+        let mut coarse_config: SearchConfig = SearchConfig::new(coarse_factor, 2);
+        coarse_config.score_method = config.score_method;
+        // The centroids lie within the range of the dataset, which was checked.
+        let mut coarse: Lyagushka = Lyagushka::unchecked(centroids.clone());
+        coarse.analyze(&coarse_config);
+        let ranges: Vec<(Value, Value, Option<f32>)> = coarse.anomalies.iter()
+            .filter(|group: &&Anomaly| group.kind() == AnomalyKind::Cluster)
+            .map(|group: &Anomaly| (group.start, group.end, group.z_score))
+            .collect();
+
+        let mut members: Vec<Vec<Anomaly>> = vec![Vec::new(); ranges.len()];
+        let mut ungrouped: Vec<Anomaly> = Vec::new();
+        for (cluster, centroid) in clusters.into_iter().zip(centroids) {
+            match ranges.iter().position(|&(start, end, _): &(Value, Value, Option<f32>)| start <= centroid && centroid <= end) {
+                Some(group) => members[group].push(cluster),
+                None => ungrouped.push(cluster),
+            }
+        }
+        // Every coarse cluster holds at least two centroids, so none of them is empty.
+        let groups: Vec<ClusterGroup> = ranges.into_iter().zip(members)
+            .map(|((_, _, z_score), clusters): ((Value, Value, Option<f32>), Vec<Anomaly>)| ClusterGroup {
+                start: clusters[0].start,
+                end: clusters[clusters.len() - 1].end,
+                num_elements: clusters.iter().map(|info: &Anomaly| info.num_elements).sum(),
+                z_score,
+                clusters,
+            })
+            .collect();
+        TwoScaleClusters { groups, ungrouped }
+    }
+
+    /// Sorts the dataset and counts its points in the bins between consecutive `edges`, which
+    /// may be irregular and are sorted first. Points outside the outermost edges are not counted.
+    ///
+    /// Each bin's density is scored against those of the other bins with the configured
+    /// [`ScoreMethod`], and unusually dense or sparse bins are marked with a [`BinKind`]
+    /// according to `factor` and `min_cluster_size`.
+    pub fn bin_counts(&mut self, config: &SearchConfig, edges: &[Value]) -> Vec<BinCount> {
+        self.config = Some(config.clone());
+        self.dataset.sort_unstable();
+        let mut edges: Vec<Value> = edges.to_vec();
+        edges.sort_unstable();
+        edges.dedup();
+
+        let last: usize = edges.len().saturating_sub(2);
+        let counts: Vec<usize> = edges.windows(2).enumerate()
+            .map(|(bin, w): (usize, &[Value])| {
+                let from: usize = self.dataset.partition_point(|&value: &Value| value < w[0]);
+                let to: usize = if bin == last {
+                    self.dataset.partition_point(|&value: &Value| value <= w[1])
+                } else {
+                    self.dataset.partition_point(|&value: &Value| value < w[1])
+                };
+                to - from
+            })
+            .collect();
+        let densities: Vec<f32> = edges.windows(2).zip(&counts)
+            .map(|(w, &count): (&[Value], &usize)| count as f32 / (w[1] - w[0]) as f32)
+            .collect();
+
+        let method: ScoreMethod = config.score_method;
+        let (location, scale) = method.location_and_scale(&densities);
+        let (mean_density, _) = mean_and_std_dev(&densities);
+        edges.windows(2).zip(counts).zip(&densities)
+            .map(|((w, count), &density): ((&[Value], usize), &f32)| BinCount {
+                start: w[0],
+                end: w[1],
+                count,
+                density,
+                z_score: method.score(density, &densities, location, scale),
+                kind: if density > config.factor * mean_density && count >= config.min_cluster_size {
+                    Some(BinKind::Dense)
+                } else if density < mean_density / config.factor {
+                    Some(BinKind::Sparse)
+                } else {
+                    None
+                },
+            })
+            .collect()
+    }
+
+    /// Sorts the dataset and samples its local density at `resolution` evenly spaced positions
+    /// from its smallest to its largest value, for plotting a continuous density curve.
+    ///
+    /// The density at a position is the number of points within a window centered on it, divided
+    /// by the window width: twice the distance between neighbouring positions, so that the
+    /// windows overlap and the curve is smooth. A single position lies at the center of the
+    /// range with a window covering all of it. An empty dataset has an empty profile.
+    pub fn density_profile(&mut self, resolution: usize) -> Vec<ProfilePoint> {
+        self.dataset.sort_unstable();
+        let (Some(&min), Some(&max)) = (self.dataset.first(), self.dataset.last()) else {
+            return Vec::new();
+        };
+        let range: f32 = (max - min) as f32;
+        let step: f32 = if resolution > 1 { range / (resolution - 1) as f32 } else { range };
+        // Windows are at least one unit wide, so that a dataset of a single value has a finite density.
+        let width: f32 = (2.0 * step).max(1.0);
+
+        (0..resolution)
+            .map(|i: usize| {
+                let position: f32 = if resolution > 1 { min as f32 + i as f32 * step } else { min as f32 + range / 2.0 };
+                let from: usize = self.dataset.partition_point(|&value: &Value| (value as f32) < position - width / 2.0);
+                let to: usize = self.dataset.partition_point(|&value: &Value| (value as f32) <= position + width / 2.0);
+                ProfilePoint { position, density: (to - from) as f32 / width }
+            })
+            .collect()
+    }
+
+    /// Returns the dataset-level statistics of the last analysis.
+    pub fn summary(&self) -> &DatasetSummary {
+        &self.summary
+    }
+
+    /// Returns the dataset-level statistics of the last analysis, such as its `mean_distance`
+    /// and thresholds, or `None` if no analysis has run yet.
+    pub fn last_summary(&self) -> Option<&DatasetSummary> {
+        (self.summary.algorithm_version != 0).then_some(&self.summary)
+    }
+
+    /// Writes the anomalies of the last analysis to `writer` as the same JSON array that
+    /// [`Lyagushka::to_json`] returns, serializing one anomaly at a time instead of building the
+    /// whole string first. Wrap unbuffered writers such as stdout in a `BufWriter`.
+    pub fn write_json<W: std::io::Write>(&self, writer: W) -> serde_json::Result<()> {
+        let compact: bool = self.config.as_ref().is_some_and(|config: &SearchConfig| config.compact);
+        self.write_formatted(writer, &self.anomalies, compact)
+    }
+
+    /// Writes the anomalies of the last analysis to `writer` as newline-delimited JSON, one
+    /// compact object per line, for stream processors.
+    pub fn write_ndjson<W: std::io::Write>(&self, mut writer: W) -> serde_json::Result<()> {
+        for info in &self.anomalies {
+            self.write_formatted(&mut writer, info, true)?;
+            writer.write_all(b"\n").map_err(serde_json::Error::io)?;
+        }
+        Ok(())
+    }
+
+    /// Serializes the anomalies of the last analysis as a MessagePack array of maps with the
+    /// same field names as the JSON output.
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+        rmp_serde::to_vec_named(&self.anomalies)
+    }
+
+    /// Serializes the anomalies of the last analysis as an Arrow IPC stream holding a single
+    /// record batch, with one row per anomaly and one column per field of the JSON output.
+    /// Absent optional fields are nulls.
+    #[cfg(feature = "arrow")]
+    pub fn to_arrow(&self) -> Result<Vec<u8>, arrow_schema::ArrowError> {
+        use std::sync::Arc;
+        use arrow_array::builder::{ListBuilder, PrimitiveBuilder, UInt64Builder};
+        use arrow_array::{ArrayRef, BooleanArray, Float32Array, RecordBatch, StringArray, UInt64Array};
+        use arrow_ipc::writer::StreamWriter;
+
+        let anomalies: &[Anomaly] = &self.anomalies;
+        let values = |field: fn(&Anomaly) -> Option<Value>| -> ArrayRef {
+            Arc::new(anomalies.iter().map(field).collect::<ValueArray>().with_data_type(value_data_type()))
+        };
+        let floats = |field: fn(&Anomaly) -> Option<f32>| -> ArrayRef {
+            Arc::new(anomalies.iter().map(field).collect::<Float32Array>())
+        };
+        let flags = |field: fn(&Anomaly) -> bool| -> ArrayRef {
+            Arc::new(anomalies.iter().map(|info: &Anomaly| Some(field(info))).collect::<BooleanArray>())
+        };
+        let counts = |field: fn(&Anomaly) -> Option<usize>| -> ArrayRef {
+            Arc::new(anomalies.iter().map(|info: &Anomaly| field(info).map(|count: usize| count as u64)).collect::<UInt64Array>())
+        };
+        let texts = |field: fn(&Anomaly) -> Option<&str>| -> ArrayRef {
+            Arc::new(anomalies.iter().map(field).collect::<StringArray>())
+        };
+
+        let mut elements = ListBuilder::new(PrimitiveBuilder::<ValueArrowType>::new().with_data_type(value_data_type()));
+        let mut indices = ListBuilder::new(UInt64Builder::new());
+        for info in anomalies {
+            elements.append_value(info.elements.iter().map(|value: &Value| Some(*value)));
+            match &info.indices {
+                Some(positions) => indices.append_value(positions.iter().map(|position: &usize| Some(*position as u64))),
+                None => indices.append_null(),
+            }
+        }
+
+        let batch = RecordBatch::try_from_iter([
+            ("elements", Arc::new(elements.finish()) as ArrayRef),
+            ("start", values(|info| Some(info.start))),
+            ("end", values(|info| Some(info.end))),
+            ("span_length", values(|info| Some(info.span_length))),
+            ("num_elements", Arc::new(anomalies.iter().map(|info: &Anomaly| info.num_elements as u64).collect::<UInt64Array>())),
+            ("centroid", floats(|info| Some(info.centroid))),
+            ("z_score", floats(|info| info.z_score)),
+            ("rank_score", floats(|info| info.rank_score)),
+            ("rank", counts(|info| info.rank)),
+            ("internal_gap_min", values(|info| info.internal_gap_min)),
+            ("internal_gap_max", values(|info| info.internal_gap_max)),
+            ("internal_gap_mean", floats(|info| info.internal_gap_mean)),
+            ("tightness", floats(|info| info.tightness)),
+            ("spacing_deviation", floats(|info| info.spacing_deviation)),
+            ("core_count", counts(|info| info.core_count)),
+            ("border_count", counts(|info| info.border_count)),
+            ("empty_start", values(|info| info.empty_start)),
+            ("empty_end", values(|info| info.empty_end)),
+            ("gap_center", floats(|info| info.gap_center)),
+            ("duration", texts(|info| info.duration.as_deref())),
+            ("dead_zone", flags(|info| info.dead_zone)),
+            ("edge", flags(|info| info.edge)),
+            ("weak", flags(|info| info.weak)),
+            ("elements_truncated", flags(|info| info.elements_truncated)),
+            ("indices", Arc::new(indices.finish())),
+            ("explanation", texts(|info| info.explanation.as_deref())),
+            ("stability", floats(|info| info.stability)),
+            ("p_value", floats(|info| info.p_value)),
+            ("significant", Arc::new(anomalies.iter().map(|info: &Anomaly| info.significant).collect::<BooleanArray>())),
+        ])?;
+
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut writer = StreamWriter::try_new(&mut buffer, &batch.schema())?;
+        writer.write(&batch)?;
+        writer.finish()?;
+        Ok(buffer)
+    }
+
+    /// Replaces the sorted dataset, and the input positions of its values if captured, with a
+    /// sample that keeps every point with probability `fraction`, and returns the originals.
+    fn sample(&mut self, fraction: f32, seed: u64) -> (Vec<Value>, Option<Vec<usize>>) {
+        let mut rng = SplitMix64::new(seed);
+        let kept: Vec<usize> = (0..self.dataset.len()).filter(|_| rng.next_f32() < fraction).collect();
+        self.retain_positions(&kept)
+    }
+
+    /// Replaces the sorted dataset, and the input positions of its values if captured, with
+    /// the values within `[lo, hi]`, and returns the originals.
+    fn restrict(&mut self, lo: Value, hi: Value) -> (Vec<Value>, Option<Vec<usize>>) {
+        let from: usize = self.dataset.partition_point(|&value: &Value| value < lo);
+        let to: usize = self.dataset.partition_point(|&value: &Value| value <= hi).max(from);
+        self.retain_positions(&(from..to).collect::<Vec<usize>>())
+    }
+
+    /// Replaces the sorted dataset, and the input positions of its values if captured, with
+    /// the values at the sorted positions `kept`, and returns the originals.
+    fn retain_positions(&mut self, kept: &[usize]) -> (Vec<Value>, Option<Vec<usize>>) {
+        let dataset: Vec<Value> = kept.iter().map(|&position: &usize| self.dataset[position]).collect();
+        let input_indices: Option<Vec<usize>> = self.input_indices.as_ref()
+            .map(|indices: &Vec<usize>| kept.iter().map(|&position: &usize| indices[position]).collect());
+        (std::mem::replace(&mut self.dataset, dataset), std::mem::replace(&mut self.input_indices, input_indices))
+    }
+
+    /// Scans the sorted dataset again at `STABILITY_STEPS` factors evenly spaced across
+    /// `1 ± band` times the configured ones and sets the `stability` of every anomaly to the
+    /// fraction of scans that detected it. The anomalies and summary of the analysis are kept.
+    ///
+    /// `max_passes` reduces the number of factors, a single one being the configured factor
+    /// itself, and once `timeout` has passed since the analysis `started` no further scan is
+    /// run. Either is recorded in the summary; without any scan, no `stability` is set.
+    fn score_stability(&mut self, config: &SearchConfig, band: f32, started: Instant) {
+        // The anomalies are ordered by position. Clusters never overlap each other and gaps
+        // are told apart by their bounds, so the detections of a scan are looked up by binary
+        // search as it emits them, without holding on to its anomalies.
+        let anomalies: &[Anomaly] = &self.anomalies;
+        let of_kind = |kind: AnomalyKind| -> Vec<usize> {
+            (0..anomalies.len()).filter(|&position: &usize| anomalies[position].kind() == kind).collect()
+        };
+        let (clusters, gaps): (Vec<usize>, Vec<usize>) = (of_kind(AnomalyKind::Cluster), of_kind(AnomalyKind::Gap));
+        let bounds = |position: &usize| -> (Value, Value) { (anomalies[*position].start, anomalies[*position].end) };
+        let mut detections: Vec<usize> = vec![0; anomalies.len()];
+        let steps: usize = config.max_passes.map_or(STABILITY_STEPS, |max: usize| max.min(STABILITY_STEPS));
+        let deadline: Option<Instant> = config.timeout
+            .and_then(|seconds: f32| std::time::Duration::try_from_secs_f32(seconds).ok())
+            .map(|timeout: std::time::Duration| started + timeout);
+
+        let mut passes: usize = 0;
+        for step in 0..steps {
+            if deadline.is_some_and(|deadline: Instant| Instant::now() >= deadline) {
+                debug!("Stability analysis timed out after {} of {} scans", passes, steps);
+                break;
+            }
+            let scale: f32 = if steps == 1 { 1.0 } else { 1.0 - band + 2.0 * band * step as f32 / (steps - 1) as f32 };
+            let mut variant: SearchConfig = config.clone();
+            variant.factor *= scale;
+            variant.enter_factor = config.enter_factor.map(|factor: f32| factor * scale);
+            variant.exit_factor = config.exit_factor.map(|factor: f32| factor * scale);
+
+            let mut detected: Vec<bool> = vec![false; anomalies.len()];
+            self.scan_with(&variant, |other: Anomaly| {
+                let matches: &[usize] = match other.kind() {
+                    AnomalyKind::Cluster => {
+                        let first: usize = clusters.partition_point(|position: &usize| bounds(position).1 < other.start);
+                        let last: usize = first + clusters[first..].partition_point(|position: &usize| bounds(position).0 <= other.end);
+                        &clusters[first..last]
+                    }
+                    AnomalyKind::Gap => {
+                        let first: usize = gaps.partition_point(|position: &usize| bounds(position) < (other.start, other.end));
+                        let last: usize = first + gaps[first..].partition_point(|position: &usize| bounds(position) == (other.start, other.end));
+                        &gaps[first..last]
+                    }
+                };
+                for &position in matches.iter().filter(|&&position: &&usize| anomalies[position].detected_as(&other)) {
+                    detected[position] = true;
+                }
+            });
+            for (count, detected) in detections.iter_mut().zip(detected) {
+                *count += usize::from(detected);
+            }
+            passes += 1;
+        }
+
+        self.summary.stability_passes = Some(passes);
+        self.summary.pass_limit_reached = passes < STABILITY_STEPS;
+        if passes > 0 {
+            for (info, count) in self.anomalies.iter_mut().zip(detections) {
+                info.stability = Some(count as f32 / passes as f32);
+            }
+        }
+    }
+
+    /// Buckets the distances between consecutive points of the sorted dataset.
+    fn distance_histogram(&self, config: &SearchConfig, buckets: usize) -> DistanceHistogram {
+        let thresholds: Thresholds = self.thresholds(config);
+        let distances: Vec<f32> = self.dataset.windows(2).map(|w| (w[1] - w[0]) as f32).collect();
+        let buckets: usize = buckets.max(1);
+        let min: f32 = distances.iter().copied().fold(f32::INFINITY, f32::min);
+        let max: f32 = distances.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let width: f32 = if max > min { (max - min) / buckets as f32 } else { 1.0 };
+
+        let mut counts: Vec<usize> = vec![0; if distances.is_empty() { 0 } else { buckets }];
+        for distance in &distances {
+            let bucket: usize = (((distance - min) / width) as usize).min(buckets - 1);
+            counts[bucket] += 1;
+        }
+
+        DistanceHistogram {
+            cluster_threshold: thresholds.cluster_threshold,
+            cluster_exit_threshold: thresholds.cluster_exit_threshold,
+            gap_threshold: thresholds.gap_threshold,
+            buckets: counts.into_iter().enumerate()
+                .map(|(i, count): (usize, usize)| HistogramBucket {
+                    start: min + i as f32 * width,
+                    end: min + (i + 1) as f32 * width,
+                    count,
+                })
+                .collect(),
+        }
+    }
+
+    /// Finds the lag at which the autocorrelation of the distances between consecutive points
+    /// peaks and, if the peak is at least `PERIODICITY_THRESHOLD`, returns it in value units:
+    /// the mean distance between points that many positions apart. Evenly spaced points have
+    /// the period of their spacing.
+    fn detect_period(&self) -> Option<f32> {
+        let distances: Vec<f32> = self.dataset.windows(2).map(|w| (w[1] - w[0]) as f32).collect();
+        if distances.is_empty() {
+            return None;
+        }
+        let mean: f32 = distances.iter().sum::<f32>() / distances.len() as f32;
+        let variance: f32 = distances.iter().map(|distance: &f32| (distance - mean).powi(2)).sum();
+        if variance == 0.0 {
+            return (mean > 0.0).then_some(mean);
+        }
+
+        let autocorrelation = |lag: usize| -> f32 {
+            distances.iter().zip(&distances[lag..])
+                .map(|(a, b): (&f32, &f32)| (a - mean) * (b - mean))
+                .sum::<f32>() / variance
+        };
+        (1..=distances.len() / 2)
+            .map(|lag: usize| (lag, autocorrelation(lag)))
+            .max_by(|a: &(usize, f32), b: &(usize, f32)| a.1.total_cmp(&b.1))
+            .filter(|&(_, correlation): &(usize, f32)| correlation >= PERIODICITY_THRESHOLD)
+            .map(|(lag, _): (usize, f32)| {
+                let spans: Vec<f32> = self.dataset.iter().zip(&self.dataset[lag..])
+                    .map(|(a, b): (&Value, &Value)| (b - a) as f32)
+                    .collect();
+                spans.iter().sum::<f32>() / spans.len() as f32
+            })
+    }
+
+    /// Whether the sorted dataset consists of two or more copies of a single value.
+    fn is_zero_range(&self) -> bool {
+        lyagushka_core::is_zero_range(&self.dataset)
+    }
+
+    /// Derives the clustering and gap thresholds for the sorted dataset, see
+    /// [`lyagushka_core::thresholds`].
+    fn thresholds(&self, config: &SearchConfig) -> Thresholds {
+        lyagushka_core::thresholds(&self.dataset, config.factor, config.enter_factor, config.exit_factor, config.adaptive,
+            |start: Value, end: Value| config.is_masked(start, end))
+    }
+
+    /// Serializes `value` as pretty-printed JSON, or compact JSON if the last analysis asked for it.
+    fn serialize<T: Serialize>(&self, value: &T) -> String {
+        let compact: bool = self.config.as_ref().is_some_and(|config: &SearchConfig| config.compact);
+        let mut buffer: Vec<u8> = Vec::new();
+        match self.write_formatted(&mut buffer, value, compact) {
+            Ok(()) => String::from_utf8(buffer).unwrap_or_else(|_| "Failed to serialize data".to_string()),
+            Err(_) => "Failed to serialize data".to_string(),
+        }
+    }
+
+    /// Writes `value` as JSON, canonical, with its centroids rounded and its indentation set if
+    /// the last analysis asked for it.
+    fn write_formatted<W: std::io::Write, T: Serialize>(&self, writer: W, value: &T, compact: bool) -> serde_json::Result<()> {
+        use serde_json::ser::{CompactFormatter, PrettyFormatter, Serializer};
+        let indent: &[u8] = self.config.as_ref()
+            .and_then(|config: &SearchConfig| config.indent.as_deref())
+            .unwrap_or("  ")
+            .as_bytes();
+        let rounding: Option<CentroidRounding> = self.config.as_ref().and_then(|config: &SearchConfig| config.round_centroid);
+        if self.config.as_ref().is_some_and(|config: &SearchConfig| config.canonical) {
+            // The maps of a `serde_json::Value` are ordered by key.
+            let value: serde_json::Value = serde_json::to_value(value)?;
+            let precision: Option<usize> = Some(CANONICAL_PRECISION);
+            return match compact {
+                true => value.serialize(&mut Serializer::with_formatter(writer, FloatFormatter::new(CompactFormatter, rounding, precision))),
+                false => value.serialize(&mut Serializer::with_formatter(writer, FloatFormatter::new(PrettyFormatter::with_indent(indent), rounding, precision))),
+            };
+        }
+        match rounding {
+            None if compact => serde_json::to_writer(writer, value),
+            None => value.serialize(&mut Serializer::with_formatter(writer, PrettyFormatter::with_indent(indent))),
+            Some(_) if compact => {
+                value.serialize(&mut Serializer::with_formatter(writer, FloatFormatter::new(CompactFormatter, rounding, None)))
+            }
+            Some(_) => {
+                value.serialize(&mut Serializer::with_formatter(writer, FloatFormatter::new(PrettyFormatter::with_indent(indent), rounding, None)))
+            }
+        }
+    }
+
+    /// Replaces the dataset with the projection of the two-dimensional points onto `axis`.
+    fn project(&mut self, axis: Axis) -> Result<(), Error> {
+        let points: &Vec<(Value, Value)> = self.points.as_ref().ok_or(Error::NotTwoDimensional)?;
+        self.dataset = points.iter()
+            .map(|point: &(Value, Value)| match axis {
+                Axis::X => point.0,
+                Axis::Y => point.1,
+            })
+            .collect();
+        Ok(())
+    }
+}
+
+/// Combines the anomalies of analyses run on shards of a dataset split by value range.
+///
+/// The parts are concatenated and ordered by `start`. For clusters to be joined across a
+/// shard boundary, adjacent shards must share their boundary value, i.e. both contain the
+/// points at the value where the dataset was split. The boundary-merge rule is then:
+///
+/// - Two consecutive clusters merge if the second starts at or before the end of the first.
+///   Elements of the second cluster at or below the end of the first are the shared boundary
+///   points and are dropped, so they aren't counted twice.
+/// - Two consecutive gaps with the same flags merge if they overlap, which happens when shards
+///   overlap by more than the boundary value. Gaps merely touching at a point stay separate,
+///   as the point between them is data.
+///
+/// Merged anomalies have their fields recomputed from the union, with sorted `elements` and
+/// without a `z_score`, since the scores of the parts were measured against different shards.
+#[pyfunction]
+pub fn merge_results(parts: Vec<Vec<Anomaly>>) -> Vec<Anomaly> {
+    let mut anomalies: Vec<Anomaly> = parts.into_iter().flatten().collect();
+    anomalies.sort_by_key(|info: &Anomaly| info.start);
+
+    let mut merged: Vec<Anomaly> = Vec::with_capacity(anomalies.len());
+    for info in anomalies {
+        let previous: Option<&mut Anomaly> = merged.last_mut();
+        match previous {
+            Some(previous) if previous.kind() == AnomalyKind::Cluster && info.kind() == AnomalyKind::Cluster && info.start <= previous.end => {
+                let mut elements: Vec<Value> = previous.elements.clone();
+                elements.extend(info.elements.iter().filter(|&&value: &&Value| value > previous.end));
+                elements.sort_unstable();
+                *previous = Anomaly::new(&elements);
+            }
+            Some(previous) if previous.kind() == AnomalyKind::Gap && info.kind() == AnomalyKind::Gap && info.start < previous.end
+                && previous.dead_zone == info.dead_zone && previous.edge == info.edge => {
+                let mut gap: Anomaly = Anomaly::gap(previous.start, previous.end.max(info.end));
+                gap.dead_zone = info.dead_zone;
+                gap.edge = info.edge;
+                *previous = gap;
+            }
+            _ => merged.push(info),
+        }
+    }
+    merged
+}
+
+/// Anomalies that appeared, disappeared or changed between two analyses, see [`diff`].
+#[pyclass(get_all)]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AnomalyDiff {
+    /// Anomalies of the current analysis without a match in the previous one.
+    pub added: Vec<Anomaly>,
+    /// Anomalies of the previous analysis without a match in the current one.
+    pub removed: Vec<Anomaly>,
+    /// Matching pairs of a previous and a current anomaly whose range or number of elements differ.
+    pub changed: Vec<(Anomaly, Anomaly)>,
+}
+
+/// Compares the anomalies of two analyses of a growing or changing dataset.
+///
+/// A previous and a current anomaly match if they are of the same type (cluster or gap, with
+/// the same `dead_zone` and `edge` flags) and either cover the same range or overlap by more
+/// than a point, so that gaps which merely meet at a point between them stay apart. Each
+/// anomaly without a match is added or removed; matching pairs are changed if they differ in
+/// `start`, `end` or `num_elements`. An anomaly that split or merged with others shows up in a
+/// pair for every anomaly it matches. Scores are not compared, as they shift with the rest
+/// of the dataset. All three lists are ordered by `start`.
+#[pyfunction]
+pub fn diff(previous: Vec<Anomaly>, current: Vec<Anomaly>) -> AnomalyDiff {
+    let matches = |a: &Anomaly, b: &Anomaly| -> bool {
+        a.kind() == b.kind() && a.dead_zone == b.dead_zone && a.edge == b.edge
+            && ((a.start, a.end) == (b.start, b.end) || (a.start < b.end && b.start < a.end))
+    };
+    let previous: AnomalyIndex = AnomalyIndex::new(previous);
+    let current: AnomalyIndex = AnomalyIndex::new(current);
+
+    let mut result: AnomalyDiff = AnomalyDiff::default();
+    for info in &previous.anomalies {
+        let counterparts: Vec<&Anomaly> = current.query_range(info.start, info.end).into_iter()
+            .filter(|other: &&Anomaly| matches(info, other))
+            .collect();
+        if counterparts.is_empty() {
+            result.removed.push(info.clone());
+        }
+        for other in counterparts {
+            if (other.start, other.end, other.num_elements) != (info.start, info.end, info.num_elements) {
+                result.changed.push((info.clone(), other.clone()));
+            }
+        }
+    }
+    for info in &current.anomalies {
+        if !previous.query_range(info.start, info.end).into_iter().any(|other: &Anomaly| matches(other, info)) {
+            result.added.push(info.clone());
+        }
+    }
+    result
+}
+
+/// Returns the [`ALGORITHM_VERSION`] of this build.
+#[pyfunction]
+pub fn algorithm_version() -> u32 {
+    ALGORITHM_VERSION
+}
+
+/// Runs [`Lyagushka::search`] on each of `datasets` and returns the JSON results in the same
+/// order, so that many small series can be analyzed in a single call.
+///
+/// With the `parallel` feature the datasets are analyzed on the rayon thread pool.
+///
+/// Fails if any of the datasets is too wide to measure, see [`Lyagushka::check_range`].
+#[pyfunction]
+pub fn analyze_batch(datasets: Vec<Vec<Value>>, factor: f32, min_cluster_size: usize) -> Result<Vec<String>, Error> {
+    let analyze = |dataset: Vec<Value>| Lyagushka::new(dataset).map(|mut zhaba: Lyagushka| zhaba.search(factor, min_cluster_size));
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        datasets.into_par_iter().map(analyze).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        datasets.into_iter().map(analyze).collect()
+    }
+}
+
+/// A small SplitMix64 pseudo-random number generator.
+///
+/// Used for synthetic data generation and dataset sampling so that a given seed always
+/// yields the same output, independent of platform or external crate versions.
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+
+    fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z: u64 = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniformly distributed float in `[0, 1)`.
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns a uniformly distributed integer in the inclusive range `[lo, hi]`.
+    fn range_inclusive(&mut self, lo: i32, hi: i32) -> i32 {
+        let width: u64 = (hi as i64 - lo as i64 + 1) as u64;
+        (lo as i64 + (self.next_u64() % width) as i64) as i32
+    }
+}
+
+/// Generates a sorted synthetic dataset of clustered integers.
+///
+/// Cluster centers are placed `gap` apart, starting at `gap`, and each cluster receives
+/// `points_per_cluster` points drawn uniformly from `center - spread ..= center + spread`.
+/// The same `seed` always produces the same dataset.
+///
+/// Fails if `spread` or `gap` is negative, or if the points of the last cluster could lie
+/// beyond the range of `i32`.
+pub fn generate_clusters(clusters: usize, points_per_cluster: usize, spread: i32, gap: i32, seed: u64) -> Result<Vec<i32>, Error> {
+    for (name, value) in [("spread", spread), ("gap", gap)] {
+        if value < 0 {
+            return Err(Error::NegativeParameter { name });
+        }
+    }
+    // Centers only grow, so the last cluster bounds all of them.
+    i32::try_from(clusters).ok()
+        .and_then(|clusters: i32| gap.checked_mul(clusters))
+        .and_then(|center: i32| center.checked_add(spread))
+        .ok_or(Error::GeneratedRangeOverflow)?;
+
+    let mut rng = SplitMix64::new(seed);
+    let mut data: Vec<i32> = Vec::with_capacity(clusters * points_per_cluster);
+    for i in 0..clusters {
+        let center: i32 = gap * (i as i32 + 1);
+        for _ in 0..points_per_cluster {
+            data.push(rng.range_inclusive(center - spread, center + spread));
+        }
+    }
+
+    data.sort_unstable();
+    Ok(data)
+}
+
+#[pymodule]
+fn lyagushka(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Lyagushka>()?;
+    m.add_class::<SearchConfig>()?;
+    m.add_class::<Anomaly>()?;
+    m.add_class::<DatasetSummary>()?;
+    m.add_class::<Baseline>()?;
+    m.add_class::<AnomalyIterator>()?;
+    m.add_class::<AnomalyIndex>()?;
+    m.add_class::<AnomalyDiff>()?;
+    m.add_class::<AnomalyKind>()?;
+    m.add_class::<Axis>()?;
+    m.add_class::<CentroidRounding>()?;
+    m.add_class::<ScoreMethod>()?;
+    m.add_class::<Correction>()?;
+    m.add_class::<TimeUnit>()?;
+    m.add_function(wrap_pyfunction!(merge_results, m)?)?;
+    m.add_function(wrap_pyfunction!(diff, m)?)?;
+    m.add_function(wrap_pyfunction!(analyze_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(algorithm_version, m)?)?;
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The clusters of an analysis of `dataset`.
+    fn clusters(dataset: Vec<Value>, config: &SearchConfig) -> Vec<Anomaly> {
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        zhaba.analyze(config);
+        zhaba.anomalies().iter().filter(|info: &&Anomaly| info.kind() == AnomalyKind::Cluster).cloned().collect()
+    }
+
+    /// The dataset [`generate_clusters`] generates from the same arguments.
+    // The generator produces `i32`, which is `Value` itself unless built with `bigint`.
+    #[allow(clippy::useless_conversion)]
+    fn generated(clusters: usize, points_per_cluster: usize, spread: i32, gap: i32, seed: u64) -> Vec<Value> {
+        generate_clusters(clusters, points_per_cluster, spread, gap, seed).unwrap().into_iter().map(Value::from).collect()
+    }
+
+    #[test]
+    fn generated_clusters_are_found() {
+        let dataset: Vec<Value> = generated(5, 20, 10, 1000, 42);
+        assert_eq!(dataset.len(), 100);
+        assert_eq!(clusters(dataset, &SearchConfig::new(1.5, 6)).len(), 5);
+    }
+
+    #[test]
+    fn generated_data_is_reproducible() {
+        assert_eq!(generate_clusters(3, 10, 5, 100, 7), generate_clusters(3, 10, 5, 100, 7));
+    }
+
+    #[test]
+    fn generator_rejects_invalid_parameters() {
+        assert_eq!(generate_clusters(3, 10, -1, 100, 0), Err(Error::NegativeParameter { name: "spread" }));
+        assert_eq!(generate_clusters(3, 10, 1, -100, 0), Err(Error::NegativeParameter { name: "gap" }));
+        assert_eq!(generate_clusters(3, 10, 10, 1_000_000_000, 0), Err(Error::GeneratedRangeOverflow));
+        assert_eq!(generate_clusters(2, 1, 1, i32::MAX / 2, 0).map(|data: Vec<i32>| data.len()), Ok(2));
+    }
+
+    /// Logger collecting every record, for the tests of the `logging` feature.
+    #[cfg(feature = "logging")]
+    struct CapturingLogger {
+        records: std::sync::Mutex<Vec<(log::Level, String)>>,
+    }
+
+    #[cfg(feature = "logging")]
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.records.lock().unwrap().push((record.level(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[cfg(feature = "logging")]
+    static LOGGER: CapturingLogger = CapturingLogger { records: std::sync::Mutex::new(Vec::new()) };
+
+    #[test]
+    #[cfg(feature = "logging")]
+    fn analysis_logs_diagnostics() {
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50, 51, 52, 100]).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.0, 2));
+
+        // Other tests may log concurrently, so only the presence of the records is checked.
+        let records = LOGGER.records.lock().unwrap();
+        let logged = |level: log::Level, prefix: &str| records.iter().any(|(l, message)| *l == level && message.starts_with(prefix));
+        assert!(logged(log::Level::Debug, "Scanning 7 points: mean distance 16.5"));
+        assert!(logged(log::Level::Trace, "Cluster of 3 points from 1 to 3"));
+        assert!(logged(log::Level::Trace, "Gap from 3 to 50"));
+        assert!(logged(log::Level::Debug, "Analysis finished in"));
+    }
+
+    #[test]
+    fn deltas_separate_step_size_regimes() {
+        // Steps of 1 followed by steps of 10.
+        let series: Vec<Value> = (0..10).chain((1..=10).map(|step: Value| 9 + 10 * step)).collect();
+        let mut zhaba: Lyagushka = Lyagushka::from_deltas(series).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.5, 3));
+        let ranges: Vec<(Value, Value, usize)> = zhaba.anomalies().iter()
+            .map(|info: &Anomaly| (info.start, info.end, info.num_elements))
+            .collect();
+        assert_eq!(ranges, vec![(1, 1, 9), (1, 10, 0), (10, 10, 10)]);
+    }
+
+    #[test]
+    fn overflowing_deltas_are_an_error() {
+        assert_eq!(
+            Lyagushka::from_deltas(vec![0, Value::MIN, Value::MAX]).err(),
+            Some(Error::RangeOverflow { min: Value::MIN, max: Value::MAX })
+        );
+    }
+
+    #[test]
+    fn negative_and_zero_crossing_datasets_match_a_shifted_positive_one() {
+        let dataset: Vec<Value> = vec![10, 11, 12, 13, 40, 42, 44, 46, 48, 90, 91, 92, 93, 94, 95, 150];
+        let config: SearchConfig = SearchConfig::new(1.5, 3);
+        let anomalies = |shift: Value| -> Vec<Anomaly> {
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset.iter().map(|value: &Value| value - shift).collect()).unwrap();
+            zhaba.analyze(&config);
+            zhaba.anomalies().to_vec()
+        };
+        let positive: Vec<Anomaly> = anomalies(0);
+        assert!(positive.len() > 3);
+        // All negative, and crossing zero inside the second cluster.
+        for shift in [1000, 44] {
+            let shifted: Vec<Anomaly> = anomalies(shift);
+            assert_eq!(shifted.len(), positive.len());
+            for (info, original) in shifted.iter().zip(&positive) {
+                assert_eq!((info.start, info.end), (original.start - shift, original.end - shift));
+                assert_eq!((info.span_length, info.num_elements), (original.span_length, original.num_elements));
+                assert_eq!(info.centroid, original.centroid - shift as f32);
+                assert_eq!(info.z_score, original.z_score);
+            }
+        }
+    }
+
+    #[test]
+    fn negative_centroids_round_by_their_sign() {
+        let info: Anomaly = Anomaly::new(&[-3, -2]);
+        assert_eq!((info.span_length, info.centroid), (1, -2.5));
+        assert_eq!(CentroidRounding::Nearest.apply(info.centroid), -3);
+        assert_eq!(CentroidRounding::Floor.apply(info.centroid), -3);
+        assert_eq!(CentroidRounding::Ceil.apply(info.centroid), -2);
+        assert_eq!(Anomaly::new(&[-7, 3]).centroid, -2.0);
+    }
+
+    #[test]
+    fn constructors_reject_unmeasurable_ranges() {
+        let overflow: Error = Error::RangeOverflow { min: Value::MIN, max: Value::MAX };
+        assert_eq!(Lyagushka::new(vec![Value::MAX, 0, Value::MIN]).err(), Some(overflow.clone()));
+        assert_eq!(Lyagushka::from_frequencies(vec![(Value::MIN, 1), (Value::MAX, 2)]).err(), Some(overflow.clone()));
+        assert_eq!(Lyagushka::new_2d(vec![(0, Value::MIN), (1, Value::MAX)]).err(), Some(overflow.clone()));
+        assert_eq!(analyze_batch(vec![vec![1, 2], vec![Value::MIN, Value::MAX]], 1.5, 2).err(), Some(overflow));
+        // All-negative datasets reaching the minimum still fit.
+        assert!(Lyagushka::new(vec![Value::MIN, -1]).is_ok());
+    }
+
+    #[test]
+    fn touching_cluster_and_gap_do_not_overlap() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50, 51, 52]).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.0, 2));
+        let ranges: Vec<(Value, Value)> = zhaba.anomalies().iter().map(|info: &Anomaly| (info.start, info.end)).collect();
+        assert_eq!(ranges, vec![(1, 3), (3, 50), (50, 52)]);
+        assert_eq!(zhaba.check_disjoint(), Ok(()));
+
+        // A gap reaching into the cluster after it shares interior points with it.
+        zhaba.anomalies[1] = Anomaly::gap(3, 51);
+        assert_eq!(zhaba.check_disjoint(), Err(Error::Overlap { first: 1, second: 2 }));
+    }
+
+    #[test]
+    fn algorithm_version_is_reported() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50, 51, 52, 100]).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.0, 2));
+        assert_eq!(zhaba.summary().algorithm_version, ALGORITHM_VERSION);
+        for output in [zhaba.to_report(), zhaba.to_bundle()] {
+            let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+            let reported = value.get("summary").unwrap_or(&value)["algorithm_version"].as_u64();
+            assert_eq!(reported, Some(ALGORITHM_VERSION as u64), "{}", output);
+        }
+    }
+
+    #[test]
+    fn anomalies_sharing_a_start_are_ordered_deterministically() {
+        let dataset: Vec<Value> = vec![5, 5, 5, 40, 41, 42, 43, 80, 80, 80];
+        let config: SearchConfig = SearchConfig::new(1.5, 3);
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+        zhaba.analyze(&config);
+        let bounds: Vec<(Value, Value, AnomalyKind)> = zhaba.anomalies().iter().map(|info: &Anomaly| (info.start, info.end, info.kind())).collect();
+        assert_eq!(bounds[..2], [(5, 5, AnomalyKind::Cluster), (5, 40, AnomalyKind::Gap)]);
+
+        let mut reversed: Lyagushka = Lyagushka::new(dataset.into_iter().rev().collect()).unwrap();
+        reversed.analyze(&config);
+        assert_eq!(reversed.to_json(), zhaba.to_json());
+
+        let mut shuffled: Vec<Anomaly> = zhaba.anomalies().to_vec();
+        shuffled.reverse();
+        shuffled.sort_by_key(Anomaly::sort_key);
+        assert_eq!(zhaba.serialize(&shuffled), zhaba.to_json());
+    }
+
+    #[test]
+    fn fused_path_matches_the_general_path() {
+        let mut sorted: Vec<Value> = generated(6, 30, 15, 400, 7);
+        sorted.extend([2950, 2960, 2961, 2990]);
+        sorted.sort_unstable();
+        let variants: [fn(&mut SearchConfig); 7] = [
+            |_| {},
+            |config| config.log_gap_spans = true,
+            |config| config.log_densities = true,
+            |config| config.unified_score = true,
+            |config| (config.report_dead_zones, config.edge_gaps) = (true, true),
+            |config| (config.density_smoothing, config.density_weight, config.shrinkage) = (Some(0.5), Some(0.7), true),
+            |config| config.explain = true,
+        ];
+        for dataset in [sorted, vec![7; 5], Vec::new()] {
+            for variant in variants {
+                let mut config: SearchConfig = SearchConfig::new(1.5, 5);
+                variant(&mut config);
+                let mut general: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+                general.analyze(&config);
+                config.assume_sorted = true;
+                let mut fused: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+                fused.analyze(&config);
+                assert_eq!(fused.to_report(), general.to_report());
+            }
+        }
+    }
+
+    #[test]
+    fn values_within_epsilon_coincide() {
+        let readings: Vec<Value> = vec![1000, 1002, 1001, 1000, 1002, 1500, 1503, 1501, 1502, 3000];
+        let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+        let spans = |config: &SearchConfig| -> Vec<(Value, Value, Vec<Value>)> {
+            clusters(readings.clone(), config).into_iter().map(|info: Anomaly| (info.start, info.span_length, info.elements)).collect()
+        };
+        assert_eq!(spans(&config).iter().map(|cluster| cluster.1).collect::<Vec<Value>>(), [2, 3]);
+        config.epsilon = Some(3);
+        assert_eq!(spans(&config), [(1000, 0, vec![1000; 5]), (1500, 0, vec![1500; 4])]);
+
+        // Runs are anchored at their first value rather than chained.
+        assert_eq!(snap_coincident(&[0, 2, 4, 6, 7], 2), [0, 0, 4, 4, 7]);
+        let mut zhaba: Lyagushka = Lyagushka::new(readings.clone()).unwrap();
+        zhaba.analyze(&config);
+        assert_eq!(zhaba.dataset().iter().filter(|&&value: &&Value| value == 1002).count(), 2, "the dataset is restored");
+    }
+
+    #[test]
+    fn canonical_output_sorts_keys_and_fixes_decimals() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50, 51, 52, 100]).unwrap();
+        let mut config: SearchConfig = SearchConfig::new(1.0, 2);
+        config.canonical = true;
+        zhaba.analyze(&config);
+        let json: String = zhaba.to_json();
+        let keys: Vec<&str> = json.lines()
+            .take_while(|line: &&str| !line.starts_with("  }"))
+            .filter_map(|line: &str| line.trim().strip_prefix('"')?.split('"').next())
+            .collect();
+        assert!(keys.len() > 5 && keys.windows(2).all(|w: &[&str]| w[0] < w[1]), "{:?}", keys);
+        assert!(json.contains("\"centroid\": 2.000000"), "{}", json);
+    }
+
+    #[test]
+    #[cfg(feature = "bigint")]
+    fn canonical_output_keeps_values_beyond_64_bits() {
+        let base: Value = 1 << 100;
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![base, base + 1, base + 2, base + 50, base + 51, base + 52]).unwrap();
+        let mut config: SearchConfig = SearchConfig::new(1.0, 2);
+        config.canonical = true;
+        zhaba.analyze(&config);
+        let json: String = zhaba.to_json();
+        assert!(json.contains(&format!("\"start\": {},", base + 50)), "{}", json);
+    }
+
+    #[test]
+    fn parsing_matches_the_command_line_tool() {
+        let text: &str = "# header\n12\n\n  7 \nabc\n99999999999999999999999999999999999999999\n-3\n";
+        let input: ParsedInput = parse_lines(text.lines(), &ParseOptions::default()).unwrap();
+        assert_eq!(input.values, [(12, 1), (7, 1), (-3, 1)]);
+        assert_eq!(input.out_of_range, [6]);
+        assert_eq!(Lyagushka::parse(text, false).unwrap().dataset(), [12, 7, -3]);
+        assert_eq!(Lyagushka::parse(text, true).err(), Some(Error::InvalidLine { line: 5, content: "abc".to_string() }));
+        assert_eq!(text.parse::<Lyagushka>().err(), Lyagushka::parse(text, true).err());
+
+        let nonfinite: &str = "1\nNaN\n2\ninf\n";
+        assert_eq!(Lyagushka::parse(nonfinite, false).err(), Some(Error::NonFinite { lines: vec![2, 4] }));
+        let dropping: ParseOptions = ParseOptions { on_nonfinite: NonFinitePolicy::Drop, ..ParseOptions::default() };
+        assert_eq!(parse_lines(nonfinite.lines(), &dropping).unwrap().nonfinite, [2, 4]);
+
+        let options: ParseOptions = ParseOptions { comment_prefix: Some("//".to_string()), grouped: true, ..ParseOptions::default() };
+        assert_eq!(Lyagushka::parse_with("// note\n1,000\n2_500\n", &options).unwrap().dataset(), [1000, 2500]);
+        let options: ParseOptions = ParseOptions { jsonl_field: Some("v".to_string()), ..ParseOptions::default() };
+        assert_eq!(Lyagushka::parse_with("{\"v\": 4}\n5\n{\"w\": 6}\n", &options).unwrap().dataset(), [4, 5]);
+        let options: ParseOptions = ParseOptions { weighted: true, strict: true, ..ParseOptions::default() };
+        assert_eq!(Lyagushka::parse_with("3 2\n1,5\n", &options).unwrap().dataset(), [1, 3]);
+        assert_eq!(Lyagushka::parse_with("3\n", &options).err(), Some(Error::MissingFrequency { line: 1, content: "3".to_string() }));
+    }
+
+    #[test]
+    fn capped_output_is_marked_truncated() {
+        // Pairs of points ten apart form a thousand tiny clusters with a gap between each two.
+        let dataset: Vec<Value> = (0..1000).flat_map(|pair: Value| [11 * pair, 11 * pair + 1]).collect();
+        let mut config: SearchConfig = SearchConfig::new(1.5, 2);
+        config.max_clusters = Some(5);
+        config.max_gaps = Some(3);
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        zhaba.analyze(&config);
+        let kinds: Vec<AnomalyKind> = zhaba.anomalies().iter().map(Anomaly::kind).collect();
+        assert_eq!(kinds.iter().filter(|&&kind: &&AnomalyKind| kind == AnomalyKind::Cluster).count(), 5);
+        assert_eq!(kinds.len(), 8);
+        let summary: &DatasetSummary = zhaba.summary();
+        assert!(summary.truncated);
+        assert_eq!((summary.num_clusters, summary.num_gaps), (1000, 999));
+        assert!(zhaba.to_report().contains("\"truncated\": true"));
+        // The scan never held more anomalies than the caps allow.
+        assert!(zhaba.max_held <= 8, "held {}", zhaba.max_held);
+    }
+
+    #[test]
+    fn capped_analysis_keeps_the_most_extreme_of_a_full_one() {
+        // Clusters of two to four points with irregular gaps between them.
+        let mut state: u64 = 3;
+        let mut dataset: Vec<Value> = Vec::new();
+        let mut start: Value = 0;
+        for _ in 0..400 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let size: Value = 2 + (state >> 62) as Value % 3;
+            dataset.extend((0..size).map(|point: Value| start + point));
+            start += 20 + (state >> 57 & 31) as Value;
+        }
+        let mut config: SearchConfig = SearchConfig::new(1.5, 2);
+        (config.rank, config.gap_centers, config.explain) = (true, true, true);
+        (config.correction, config.stability_band) = (Some(Correction::Bonferroni), Some(0.2));
+        let mut full: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+        full.analyze(&config);
+        let mut expected: Vec<Anomaly> = Vec::new();
+        for (kind, cap) in [(AnomalyKind::Cluster, 10), (AnomalyKind::Gap, 4)] {
+            let mut of_kind: Vec<&Anomaly> = full.anomalies().iter().filter(|info: &&Anomaly| info.kind() == kind).collect();
+            of_kind.sort_by_key(|info: &&Anomaly| info.rank);
+            expected.extend(of_kind.into_iter().take(cap).cloned());
+        }
+        expected.sort_by_key(Anomaly::sort_key);
+
+        (config.max_clusters, config.max_gaps) = (Some(10), Some(4));
+        let mut capped: Lyagushka = Lyagushka::new(dataset).unwrap();
+        capped.analyze(&config);
+        assert!(capped.summary().truncated);
+        assert!(capped.max_held <= 14, "held {}", capped.max_held);
+        assert!(full.max_held > 400);
+        assert_eq!(capped.anomalies().len(), expected.len());
+        for (kept, expected) in capped.anomalies().iter().zip(&expected) {
+            assert!(kept.approx_eq(expected, 0.0), "{} vs {}", kept, expected);
+            assert_eq!((&kept.explanation, kept.gap_center, kept.significant), (&expected.explanation, expected.gap_center, expected.significant));
+        }
+        let summaries: [serde_json::Value; 2] = [&full, &capped].map(|zhaba: &Lyagushka| serde_json::to_value(zhaba.summary()).unwrap());
+        let [mut full_summary, capped_summary] = summaries;
+        full_summary["truncated"] = serde_json::Value::Bool(true);
+        assert_eq!(full_summary, capped_summary);
+    }
+
+    #[test]
+    fn kind_distinguishes_clusters_from_all_kinds_of_gaps() {
+        let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+        (config.report_dead_zones, config.edge_gaps) = (true, true);
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![0, 40, 41, 42, 43, 63, 64, 65, 66, 120, 220]).unwrap();
+        zhaba.analyze(&config);
+        for info in zhaba.anomalies() {
+            let expected: AnomalyKind = if info.elements.is_empty() { AnomalyKind::Gap } else { AnomalyKind::Cluster };
+            assert_eq!(info.kind(), expected, "{}", info);
+            assert_eq!(info.to_string().starts_with("Cluster"), info.kind() == AnomalyKind::Cluster);
+        }
+        let kinds: Vec<AnomalyKind> = zhaba.anomalies().iter().map(Anomaly::kind).collect();
+        assert!(kinds.contains(&AnomalyKind::Cluster) && kinds.contains(&AnomalyKind::Gap));
+        assert!(zhaba.anomalies().iter().any(|info: &Anomaly| info.dead_zone));
+        assert_eq!(zhaba.summary().num_clusters, kinds.iter().filter(|&&kind: &&AnomalyKind| kind == AnomalyKind::Cluster).count());
+    }
+
+    #[test]
+    fn anomalies_display_as_one_line_summaries() {
+        let mut cluster: Anomaly = Anomaly::new(&[120, 128, 133, 145]);
+        cluster.z_score = Some(3.24);
+        assert_eq!(cluster.to_string(), "Cluster[120..145] n=4 z=3.2");
+        let mut gap: Anomaly = Anomaly::gap(145, 300);
+        gap.z_score = Some(-2.08);
+        assert_eq!(gap.to_string(), "Gap[145..300] span=155 z=-2.1");
+        assert_eq!(Anomaly::gap(1, 2).to_string(), "Gap[1..2] span=1 z=n/a");
+    }
+
+    /// The sample skewness of `values`.
+    fn skewness(values: &[f32]) -> f32 {
+        let (mean, std_dev) = mean_and_std_dev(values);
+        values.iter().map(|value: &f32| ((value - mean) / std_dev).powi(3)).sum::<f32>() / values.len() as f32
+    }
+
+    /// Consecutive clusters of 20 points one apart, separated by gaps of `spans`.
+    fn clusters_between(spans: &[Value]) -> Vec<Value> {
+        let mut dataset: Vec<Value> = (0..20).collect();
+        for span in spans {
+            let start: Value = dataset[dataset.len() - 1] + span;
+            dataset.extend(start..start + 20);
+        }
+        dataset
+    }
+
+    #[test]
+    fn log_gap_spans_reduce_the_skew_of_heavy_tailed_gaps() {
+        let dataset: Vec<Value> = clusters_between(&[10, 11, 12, 14, 17, 22, 35, 80, 500]);
+        let gap_scores = |log_gap_spans: bool| -> Vec<f32> {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 5);
+            config.log_gap_spans = log_gap_spans;
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+            zhaba.analyze(&config);
+            zhaba.anomalies().iter().filter(|info: &&Anomaly| info.kind() == AnomalyKind::Gap).filter_map(|info: &Anomaly| info.z_score).collect()
+        };
+        let (raw, log): (Vec<f32>, Vec<f32>) = (gap_scores(false), gap_scores(true));
+        assert_eq!((raw.len(), log.len()), (9, 9));
+        assert!(skewness(&log).abs() < skewness(&raw).abs(), "{} vs {}", skewness(&log), skewness(&raw));
+    }
+
+    #[test]
+    fn features_wrap_every_anomaly() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50, 51, 52]).unwrap();
+        let features: serde_json::Value = serde_json::from_str(&zhaba.search_features(&SearchConfig::new(1.0, 2))).unwrap();
+        assert_eq!(features["type"], "FeatureCollection");
+        let features: &Vec<serde_json::Value> = features["features"].as_array().unwrap();
+        assert_eq!(features.len(), zhaba.anomalies().len());
+        assert!(features.iter().all(|feature: &serde_json::Value| feature["type"] == "Feature"));
+        let gap: &serde_json::Value = &features[1];
+        assert_eq!(gap["range"], serde_json::json!([3, 50]));
+        assert_eq!(gap["properties"]["span_length"], 47);
+        assert_eq!(gap["properties"]["num_elements"], 0);
+        assert_eq!(gap["properties"]["elements"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn adaptive_thresholds_find_gaps_hidden_by_a_huge_one() {
+        // A moderate gap of 30 and a huge one of 10000 between three runs of points one apart.
+        let mut dataset: Vec<Value> = (0..50).collect();
+        dataset.extend(79..129);
+        dataset.extend(10128..10178);
+        let gaps = |adaptive: bool| -> Vec<(Value, Value)> {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 5);
+            config.adaptive = adaptive;
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+            zhaba.analyze(&config);
+            zhaba.anomalies().iter().filter(|info: &&Anomaly| info.kind() == AnomalyKind::Gap).map(|info: &Anomaly| (info.start, info.end)).collect()
+        };
+        assert_eq!(gaps(false), [(128, 10128)]);
+        assert_eq!(gaps(true), [(49, 79), (128, 10128)]);
+    }
+
+    #[test]
+    fn most_significant_is_the_first_by_significance() {
+        let dataset: Vec<Value> = clusters_between(&[10, 11, 12, 14, 17, 22, 35, 80, 500]);
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.5, 5));
+        let mut by_significance: Vec<Anomaly> = zhaba.anomalies().to_vec();
+        by_significance.sort_by(|a: &Anomaly, b: &Anomaly| b.z_score.unwrap().abs().total_cmp(&a.z_score.unwrap().abs()));
+
+        let most_significant: Anomaly = Lyagushka::new(dataset).unwrap().most_significant(1.5, 5).unwrap();
+        assert!(most_significant.approx_eq(&by_significance[0], 0.0));
+        assert_eq!(most_significant.span_length, 500);
+        assert!(Lyagushka::empty().most_significant(1.5, 5).is_none());
+    }
+
+    #[test]
+    fn units_label_appears_in_the_report() {
+        let mut config: SearchConfig = SearchConfig::new(1.0, 2);
+        config.units = Some("ms".to_string());
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50, 51, 52]).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&zhaba.search_report(&config)).unwrap();
+        assert_eq!(report["summary"]["units"], "ms");
+        zhaba.analyze(&SearchConfig::new(1.0, 2));
+        assert!(!zhaba.to_report().contains("\"units\""));
+    }
+
+    #[test]
+    fn unified_scores_put_clusters_and_gaps_on_one_scale() {
+        // Clusters of 20 points at spacings from 1 to 4 apart, between gaps of varied spans.
+        let mut dataset: Vec<Value> = Vec::new();
+        for (step, gap) in [(1, 100), (2, 150), (1, 400), (3, 120), (4, 900), (2, 0)] {
+            let start: Value = dataset.last().copied().unwrap_or(0);
+            dataset.extend((1..=20).map(|i: Value| start + i * step));
+            dataset.push(dataset[dataset.len() - 1] + gap);
+        }
+        dataset.pop();
+        let scores = |unified_score: bool, kind: AnomalyKind| -> Vec<f32> {
+            let mut config: SearchConfig = SearchConfig::new(2.0, 5);
+            config.unified_score = unified_score;
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+            zhaba.analyze(&config);
+            zhaba.anomalies().iter().filter(|info: &&Anomaly| info.kind() == kind).filter_map(|info: &Anomaly| info.z_score).collect()
+        };
+        for kind in [AnomalyKind::Cluster, AnomalyKind::Gap] {
+            let unified: Vec<f32> = scores(true, kind);
+            assert!(unified.len() >= 5, "{:?}", unified);
+            let (mean, std_dev) = mean_and_std_dev(&unified);
+            assert!(mean.abs() < 1e-5 && (std_dev - 1.0).abs() < 1e-5, "{:?}: {} {}", kind, mean, std_dev);
+        }
+        // Classic gap scores are negative ratios to the spread of the spans, while the widest gap
+        // scores highest in unified mode.
+        assert!(scores(false, AnomalyKind::Gap).iter().all(|z: &f32| *z < 0.0));
+        let unified_gaps: Vec<f32> = scores(true, AnomalyKind::Gap);
+        assert!(unified_gaps[4] > 0.0 && unified_gaps[4] == unified_gaps.iter().copied().fold(f32::MIN, f32::max));
+    }
+
+    #[test]
+    fn distances_at_the_thresholds_follow_the_inclusivity_options() {
+        // The mean distance is 4, so with a factor of 2 the two distances of 2 lie exactly on the
+        // cluster threshold, the distance of 8 exactly on the gap threshold and that of 4 between.
+        let dataset: Vec<Value> = vec![0, 2, 4, 12, 16];
+        let anomalies = |configure: fn(&mut SearchConfig)| -> Vec<(Value, Value, AnomalyKind, bool)> {
+            let mut config: SearchConfig = SearchConfig::new(2.0, 2);
+            configure(&mut config);
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+            zhaba.analyze(&config);
+            assert_eq!((zhaba.summary().cluster_threshold, zhaba.summary().gap_threshold), (2.0, 8.0));
+            zhaba.anomalies().iter().map(|info: &Anomaly| (info.start, info.end, info.kind(), info.dead_zone)).collect()
+        };
+        let cluster = (0, 4, AnomalyKind::Cluster, false);
+        // By default, distances on the cluster threshold join and those on the gap threshold don't.
+        assert_eq!(anomalies(|_| {}), [cluster]);
+        assert_eq!(anomalies(|config| config.inclusive_cluster_threshold = false), []);
+        assert_eq!(anomalies(|config| config.inclusive_gap_threshold = true), [cluster, (4, 12, AnomalyKind::Gap, false)]);
+        // Dead zones account for every distance that is neither.
+        assert_eq!(
+            anomalies(|config| config.report_dead_zones = true),
+            [cluster, (4, 12, AnomalyKind::Gap, true), (12, 16, AnomalyKind::Gap, true)]
+        );
+        assert_eq!(
+            anomalies(|config| (config.inclusive_gap_threshold, config.report_dead_zones) = (true, true)),
+            [cluster, (4, 12, AnomalyKind::Gap, false), (12, 16, AnomalyKind::Gap, true)]
+        );
+    }
+
+    #[test]
+    fn projections_find_the_clusters_of_their_axis_only() {
+        // Three tight groups along x, while y walks evenly across the whole range.
+        let points: Vec<(Value, Value)> = (0..30).map(|i: Value| (1000 * (i / 10) + i % 10, 7 * i)).collect();
+        let mut zhaba: Lyagushka = Lyagushka::new_2d(points).unwrap();
+        let config: SearchConfig = SearchConfig::new(1.5, 3);
+        let axes: serde_json::Value = serde_json::from_str(&zhaba.search_axes(&config).unwrap()).unwrap();
+        let clusters = |anomalies: &serde_json::Value| -> usize {
+            anomalies.as_array().unwrap().iter().filter(|info: &&serde_json::Value| info["num_elements"].as_u64() > Some(0)).count()
+        };
+        assert_eq!(clusters(&axes["x"]), 3);
+        assert_eq!(axes["y"], serde_json::json!([]));
+
+        zhaba.search_projected(Axis::X, &config).unwrap();
+        assert_eq!(zhaba.anomalies().iter().filter(|info: &&Anomaly| info.kind() == AnomalyKind::Cluster).count(), 3);
+        assert_eq!(Lyagushka::new(vec![1, 2]).unwrap().search_projected(Axis::Y, &config), Err(Error::NotTwoDimensional));
+    }
+
+    #[test]
+    fn anomalies_are_sorted_by_start() {
+        // Two clusters between sparse ends, which the scan reports as edge gaps after them.
+        let mut dataset: Vec<Value> = vec![0, 20, 40, 60];
+        dataset.extend(80..90);
+        dataset.extend(589..599);
+        dataset.extend([618, 638, 658, 678]);
+        let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+        config.edge_gaps = true;
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        zhaba.analyze(&config);
+        let ranges: Vec<(Value, Value, bool)> = zhaba.anomalies().iter().map(|info: &Anomaly| (info.start, info.end, info.edge)).collect();
+        assert_eq!(ranges, [(0, 80, true), (80, 89, false), (89, 589, false), (589, 598, false), (598, 678, true)]);
+    }
+
+    #[test]
+    fn density_smoothing_tames_tiny_clusters() {
+        // Two coincident points among clusters of ten with spans of 9.
+        let mut dataset: Vec<Value> = vec![500, 500];
+        for start in [0, 200, 800, 1000] {
+            dataset.extend(start..start + 10);
+        }
+        let scores = |smoothing: Option<f32>| -> Vec<(Value, Option<f32>)> {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 2);
+            config.density_smoothing = smoothing;
+            clusters(dataset.clone(), &config).into_iter().map(|info: Anomaly| (info.span_length, info.z_score)).collect()
+        };
+        // The infinite density of the zero span is left out, so only that cluster has no score.
+        let unsmoothed: Vec<(Value, Option<f32>)> = scores(None);
+        assert_eq!(unsmoothed.len(), 5);
+        assert_eq!(unsmoothed[2], (0, None));
+        assert!(unsmoothed.iter().enumerate().all(|(i, &(_, z)): (usize, &(Value, Option<f32>))| i == 2 || z.is_some_and(f32::is_finite)), "{:?}", unsmoothed);
+        let smoothed: Vec<(Value, Option<f32>)> = scores(Some(1.0));
+        assert_eq!(smoothed.len(), 5);
+        // Its density of 2 per unit, against 1 for the others, is two standard deviations out.
+        assert_eq!(smoothed[2].0, 0);
+        assert!((smoothed[2].1.unwrap() - 2.0).abs() < 1e-4, "{:?}", smoothed);
+    }
+
+    #[test]
+    fn input_order_is_restored_in_cluster_elements() {
+        let dataset: Vec<Value> = vec![52, 3, 50, 1, 100, 2, 51];
+        let mut config: SearchConfig = SearchConfig::new(1.0, 2);
+        config.input_order = true;
+        let found: Vec<(Vec<Value>, Option<Vec<usize>>)> = clusters(dataset, &config).into_iter()
+            .map(|info: Anomaly| (info.elements, info.indices))
+            .collect();
+        assert_eq!(found, [(vec![3, 1, 2], Some(vec![1, 3, 5])), (vec![52, 50, 51], Some(vec![0, 2, 6]))]);
+    }
+
+    #[test]
+    fn hysteresis_keeps_a_noisy_cluster_together() {
+        // A cluster with two wider steps of 3, and two regular ones, far apart.
+        let mut dataset: Vec<Value> = vec![0, 1, 2, 3, 4, 7, 8, 9, 10, 13, 14, 15];
+        dataset.extend(200..212);
+        dataset.extend(400..412);
+        let ranges = |exit_factor: Option<f32>| -> Vec<(Value, Value)> {
+            let mut config: SearchConfig = SearchConfig::new(5.0, 3);
+            config.exit_factor = exit_factor;
+            clusters(dataset.clone(), &config).iter().map(|info: &Anomaly| (info.start, info.end)).collect()
+        };
+        assert_eq!(ranges(None), [(0, 4), (7, 10), (13, 15), (200, 211), (400, 411)]);
+        assert_eq!(ranges(Some(2.0)), [(0, 15), (200, 211), (400, 411)]);
+    }
+
+    #[test]
+    fn compact_output_has_no_indentation() {
+        let mut config: SearchConfig = SearchConfig::new(1.0, 2);
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50, 51, 52]).unwrap();
+        assert!(zhaba.search_with(&config).contains("\n  "));
+        config.compact = true;
+        for output in [zhaba.search_with(&config), zhaba.to_report(), zhaba.to_bundle()] {
+            assert!(!output.contains('\n'), "{}", output);
+        }
+        let mut streamed: Vec<u8> = Vec::new();
+        zhaba.write_json(&mut streamed).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), zhaba.to_json());
+    }
+
+    #[test]
+    fn discarded_clusters_are_counted() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 50, 51, 100, 101, 102]).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.0, 3));
+        assert_eq!((zhaba.summary().num_clusters, zhaba.summary().num_discarded_clusters), (1, 2));
+        zhaba.analyze(&SearchConfig::new(1.0, 4));
+        assert_eq!((zhaba.summary().num_clusters, zhaba.summary().num_discarded_clusters), (0, 3));
+    }
+
+    #[test]
+    #[cfg(feature = "bigint")]
+    fn spans_near_the_limits_of_64_bits_are_exact() {
+        let max: Value = i64::MAX as Value;
+        let dataset: Vec<Value> = vec![-max - 1, -max, -max + 1, max - 2, max - 1, max];
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.0, 2));
+        let spans: Vec<(Value, Value, Value)> = zhaba.anomalies().iter().map(|info: &Anomaly| (info.start, info.end, info.span_length)).collect();
+        assert_eq!(spans, [(-max - 1, -max + 1, 2), (-max + 1, max - 2, 2 * max - 3), (max - 2, max, 2)]);
+    }
+
+    #[test]
+    fn explanations_name_the_score_and_its_baseline() {
+        let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+        config.explain = true;
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 4, 50, 52, 54, 56, 200]).unwrap();
+        zhaba.analyze(&config);
+        let baseline: Baseline = zhaba.baseline();
+        assert_eq!(zhaba.anomalies().len(), 4);
+        for info in zhaba.anomalies() {
+            let explanation: &str = info.explanation.as_deref().unwrap();
+            assert!(explanation.contains(&format!("(z = {:.2})", info.z_score.unwrap())), "{}", explanation);
+            let reference: String = match info.kind() {
+                AnomalyKind::Cluster => format!("mean cluster density of {:.3}", baseline.mean_density),
+                AnomalyKind::Gap => format!("standard deviation {:.1} of all anomaly spans", baseline.std_dev_span),
+            };
+            assert!(explanation.contains(&reference), "{}", explanation);
+        }
+        assert_eq!(zhaba.anomalies()[2].explanation.as_deref(), Some("Cluster density 0.667 is 1.00σ below the mean cluster density of 1.000 (z = -1.00)"));
+    }
+
+    #[test]
+    fn a_sparse_end_of_the_range_is_an_edge_gap() {
+        // A dense run and a sparse tail whose distances stay below the gap threshold.
+        let mut dataset: Vec<Value> = (0..50).collect();
+        dataset.extend((1..=50).map(|i: Value| 49 + 4 * i));
+        let gaps = |edge_gaps: bool| -> Vec<(Value, Value, bool)> {
+            let mut config: SearchConfig = SearchConfig::new(2.5, 5);
+            config.edge_gaps = edge_gaps;
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+            zhaba.analyze(&config);
+            zhaba.anomalies().iter().filter(|info: &&Anomaly| info.kind() == AnomalyKind::Gap).map(|info: &Anomaly| (info.start, info.end, info.edge)).collect()
+        };
+        assert_eq!(gaps(false), []);
+        assert_eq!(gaps(true), [(49, 249, true)]);
+    }
+
+    #[test]
+    #[cfg(feature = "msgpack")]
+    fn msgpack_output_round_trips() {
+        let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+        (config.explain, config.rank) = (true, true);
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 4, 50, 52, 54, 56, 200]).unwrap();
+        zhaba.analyze(&config);
+        let decoded: Vec<Anomaly> = rmp_serde::from_slice(&zhaba.to_msgpack().unwrap()).unwrap();
+        assert_eq!(decoded.len(), zhaba.anomalies().len());
+        assert!(decoded.iter().zip(zhaba.anomalies()).all(|(a, b): (&Anomaly, &Anomaly)| a.approx_eq(b, 0.0)));
+    }
+
+    #[test]
+    fn merged_shards_match_a_single_pass() {
+        let mut dataset: Vec<Value> = (0..10).collect();
+        dataset.extend(100..120);
+        dataset.extend(300..310);
+        let config: SearchConfig = SearchConfig::new(1.5, 5);
+        let analyze = |dataset: Vec<Value>| -> Vec<Anomaly> {
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+            zhaba.analyze(&config);
+            zhaba.anomalies().to_vec()
+        };
+        let ranges = |anomalies: &[Anomaly]| -> Vec<(Value, Value, usize)> {
+            anomalies.iter().map(|info: &Anomaly| (info.start, info.end, info.num_elements)).collect()
+        };
+        // Both shards hold the boundary value 110, in the middle of a cluster.
+        let (left, right): (Vec<Value>, Vec<Value>) = (
+            dataset.iter().copied().filter(|&value: &Value| value <= 110).collect(),
+            dataset.iter().copied().filter(|&value: &Value| value >= 110).collect(),
+        );
+        let full: Vec<Anomaly> = analyze(dataset);
+        let merged: Vec<Anomaly> = merge_results(vec![analyze(right), analyze(left)]);
+        assert_eq!(ranges(&merged), ranges(&full));
+        assert_eq!(merged[2].elements, (100..120).collect::<Vec<Value>>());
+        assert_eq!(merged[2].z_score, None);
+    }
+
+    #[test]
+    fn threshold_report_buckets_hold_every_distance() {
+        let dataset: Vec<Value> = vec![1, 2, 3, 5, 8, 13, 21, 34, 55, 89];
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&zhaba.threshold_report(&SearchConfig::new(2.0, 2), 4)).unwrap();
+        let counts: Vec<u64> = report["buckets"].as_array().unwrap().iter()
+            .map(|bucket: &serde_json::Value| bucket["count"].as_u64().unwrap())
+            .collect();
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts.iter().sum::<u64>(), dataset.len() as u64 - 1);
+        assert!(report["cluster_threshold"].as_f64().unwrap() < report["gap_threshold"].as_f64().unwrap());
+    }
+
+    #[test]
+    fn approx_eq_tolerates_float_noise_only() {
+        let mut anomaly: Anomaly = Anomaly::new(&[10, 11, 13]);
+        anomaly.z_score = Some(1.5);
+        let mut nearly: Anomaly = anomaly.clone();
+        nearly.z_score = Some(1.5 + 1e-6);
+        nearly.centroid += 1e-6;
+        assert!(anomaly.approx_eq(&nearly, 1e-4));
+        assert!(!anomaly.approx_eq(&nearly, 0.0));
+
+        let mut unscored: Anomaly = anomaly.clone();
+        unscored.z_score = None;
+        let mut longer: Anomaly = anomaly.clone();
+        longer.end += 1;
+        let mut nan: Anomaly = anomaly.clone();
+        nan.z_score = Some(f32::NAN);
+        for different in [unscored, longer, Anomaly::new(&[10, 11, 14])] {
+            assert!(!anomaly.approx_eq(&different, 1e-4));
+            assert!(!different.approx_eq(&anomaly, 1e-4));
+        }
+        assert!(nan.approx_eq(&nan.clone(), 0.0));
+    }
+
+    #[test]
+    fn boundaries_are_sorted_and_labeled() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![50, 1, 52, 3, 51, 2]).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.0, 2));
+        let boundaries: Vec<(Value, BoundaryKind)> = zhaba.boundaries().iter()
+            .map(|boundary: &Boundary| (boundary.position, boundary.kind))
+            .collect();
+        assert_eq!(boundaries, [
+            (1, BoundaryKind::ClusterStart),
+            (3, BoundaryKind::ClusterEnd),
+            (3, BoundaryKind::GapStart),
+            (50, BoundaryKind::GapEnd),
+            (50, BoundaryKind::ClusterStart),
+            (52, BoundaryKind::ClusterEnd),
+        ]);
+    }
+
+    #[test]
+    fn sparse_clusters_are_flagged_weak() {
+        let mut dataset: Vec<Value> = (0..20).collect();
+        dataset.extend((200..212).step_by(3));
+        dataset.extend(400..420);
+        let mut config: SearchConfig = SearchConfig::new(2.0, 3);
+        config.weak_cluster_threshold = Some(0.0);
+        let found: Vec<Anomaly> = clusters(dataset.clone(), &config);
+        assert_eq!(found.iter().map(|info: &Anomaly| (info.start, info.weak)).collect::<Vec<_>>(), [(0, false), (200, true), (400, false)]);
+        assert!(found[1].z_score.unwrap() < 0.0);
+        assert!(found[1].to_string().starts_with("WeakCluster[200..209]"), "{}", found[1]);
+
+        // Under rank scoring, the threshold is a percentile: the sparse cluster ranks at 1/6.
+        config.score_method = ScoreMethod::Rank;
+        config.weak_cluster_threshold = Some(0.5);
+        let ranked: Vec<Anomaly> = clusters(dataset.clone(), &config);
+        assert_eq!(ranked.iter().map(|info: &Anomaly| (info.start, info.weak)).collect::<Vec<_>>(), [(0, false), (200, true), (400, false)]);
+        assert!((ranked[1].rank_score.unwrap() - 1.0 / 6.0).abs() < 1e-6, "{:?}", ranked[1].rank_score);
+        config.weak_cluster_threshold = Some(0.1);
+        assert!(clusters(dataset.clone(), &config).iter().all(|info: &Anomaly| !info.weak));
+
+        config.weak_cluster_threshold = None;
+        assert!(clusters(dataset, &config).iter().all(|info: &Anomaly| !info.weak));
+    }
+
+    #[test]
+    fn streamed_output_matches_the_buffered_output() {
+        let streamed = |zhaba: &Lyagushka| -> String {
+            let mut out: Vec<u8> = Vec::new();
+            zhaba.write_json(&mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50, 51, 52, 200, 201, 202]).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.0, 2));
+        assert!(zhaba.anomalies().len() > 1);
+        assert_eq!(streamed(&zhaba), zhaba.to_json());
+        assert_eq!(streamed(&zhaba), serde_json::to_string_pretty(zhaba.anomalies()).unwrap());
+
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 4]).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.0, 100));
+        assert!(zhaba.anomalies().is_empty());
+        assert_eq!(streamed(&zhaba), zhaba.to_json());
+    }
+
+    #[test]
+    fn borderline_clusters_are_less_stable() {
+        let mut dataset: Vec<Value> = (0..20).collect();
+        dataset.extend((200..240).step_by(4));
+        dataset.extend(400..420);
+        let mut config: SearchConfig = SearchConfig::new(2.0, 3);
+        config.stability_band = Some(0.3);
+        // The middle cluster is spaced close to the cluster threshold and is lost at the high factors.
+        let stability: Vec<(Value, f32)> = clusters(dataset, &config).iter()
+            .map(|info: &Anomaly| (info.start, info.stability.unwrap()))
+            .collect();
+        assert_eq!(stability.iter().map(|(start, _)| *start).collect::<Vec<Value>>(), [0, 200, 400]);
+        assert!(stability[0].1 > 0.99 && stability[2].1 > 0.99, "{:?}", stability);
+        assert!(stability[1].1 < 0.9, "{:?}", stability);
+    }
+
+    #[test]
+    fn sampling_everything_matches_the_full_run() {
+        let dataset: Vec<Value> = generated(20, 30, 10, 500, 7);
+        let config: SearchConfig = SearchConfig::new(1.5, 6);
+        let mut full: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+        full.analyze(&config);
+        // Fractions outside `(0, 1]` are ignored as well, and rejected by validation.
+        for fraction in [1.0, 0.0, -0.5, 2.0, f32::NAN] {
+            let sampled_config: SearchConfig = SearchConfig { sample: Some(fraction), seed: 3, ..config.clone() };
+            assert_eq!(sampled_config.validate().is_ok(), fraction == 1.0, "{}", fraction);
+            let mut sampled: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+            sampled.analyze(&sampled_config);
+            assert_eq!(sampled.anomalies().len(), full.anomalies().len(), "{}", fraction);
+            assert!(sampled.anomalies().iter().zip(full.anomalies()).all(|(a, b): (&Anomaly, &Anomaly)| a.approx_eq(b, 0.0)), "{}", fraction);
+        }
+
+        // A sample of the same seed is the same, and its cluster sizes are scaled back up.
+        let half = || {
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+            zhaba.analyze(&SearchConfig { sample: Some(0.5), seed: 3, ..config.clone() });
+            zhaba.anomalies().to_vec()
+        };
+        let (first, second): (Vec<Anomaly>, Vec<Anomaly>) = (half(), half());
+        assert!(first.iter().zip(&second).all(|(a, b): (&Anomaly, &Anomaly)| a.approx_eq(b, 0.0)));
+        let cluster: &Anomaly = first.iter().find(|info: &&Anomaly| info.kind() == AnomalyKind::Cluster).unwrap();
+        assert!(cluster.num_elements > cluster.elements.len());
+    }
+
+    #[test]
+    fn the_dataset_is_sorted_after_a_search() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![52, 3, 50, 1, 51, 2]).unwrap();
+        assert_eq!(zhaba.dataset(), [52, 3, 50, 1, 51, 2]);
+        zhaba.search(1.0, 2);
+        assert_eq!(zhaba.dataset(), [1, 2, 3, 50, 51, 52]);
+        assert_eq!(zhaba.into_dataset(), [1, 2, 3, 50, 51, 52]);
+    }
+
+    #[test]
+    fn index_queries_match_a_linear_search() {
+        let dataset: Vec<Value> = generated(50, 20, 10, 300, 5);
+        let mut config: SearchConfig = SearchConfig::new(1.5, 4);
+        config.report_dead_zones = true;
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        zhaba.analyze(&config);
+        let index: AnomalyIndex = zhaba.index();
+        let ranges = |anomalies: Vec<&Anomaly>| -> Vec<(Value, Value, usize)> {
+            anomalies.iter().map(|info: &&Anomaly| (info.start, info.end, info.num_elements)).collect()
+        };
+        let linear = |lo: Value, hi: Value| -> Vec<(Value, Value, usize)> {
+            ranges(zhaba.anomalies().iter().filter(|info: &&Anomaly| info.start <= hi && info.end >= lo).collect())
+        };
+        let (first, last): (Value, Value) = (zhaba.dataset()[0] - 10, *zhaba.dataset().last().unwrap() + 10);
+        for value in first..=last {
+            assert_eq!(ranges(index.query(value)), linear(value, value), "at {}", value);
+        }
+        for lo in (first..=last).step_by(37) {
+            for width in [0, 5, 100, 1000] {
+                assert_eq!(ranges(index.query_range(lo, lo + width)), linear(lo, lo + width), "in [{}, {}]", lo, lo + width);
+            }
+        }
+        assert!(index.query_range(last, first).is_empty());
+    }
+
+    #[test]
+    fn large_clusters_leave_out_their_elements() {
+        let mut dataset: Vec<Value> = (0..5).collect();
+        dataset.extend(100..150);
+        let mut config: SearchConfig = SearchConfig::new(2.0, 3);
+        config.max_elements = Some(10);
+        let found: Vec<Anomaly> = clusters(dataset, &config);
+        assert_eq!(found.len(), 2);
+        assert_eq!((found[0].elements.len(), found[0].elements_truncated), (5, false));
+        assert_eq!((found[1].elements.len(), found[1].num_elements, found[1].elements_truncated), (0, 50, true));
+        assert!(serde_json::to_string(&found[1]).unwrap().contains("\"elements_truncated\":true"));
+        assert!(!serde_json::to_string(&found[0]).unwrap().contains("elements_truncated"));
+    }
+
+    #[test]
+    fn higher_sensitivity_reports_at_least_as_many_gaps_and_clustered_points() {
+        let mut dataset: Vec<Value> = generated(30, 20, 15, 400, 11);
+        dataset.extend([20000, 20003, 20010, 20030, 20070, 20150, 20300, 20600]);
+        // The number of clusters itself can drop, as looser clusters merge.
+        let counts: Vec<(usize, usize)> = (0..=10)
+            .map(|step: i32| {
+                let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+                zhaba.analyze(&SearchConfig::with_sensitivity(step as f32 / 10.0, 5));
+                let gaps: usize = zhaba.anomalies().iter().filter(|info: &&Anomaly| info.kind() == AnomalyKind::Gap).count();
+                let clustered: usize = zhaba.anomalies().iter().map(|info: &Anomaly| info.elements.len()).sum();
+                (gaps, clustered)
+            })
+            .collect();
+        assert!(counts.windows(2).all(|w: &[(usize, usize)]| w[0].0 <= w[1].0 && w[0].1 <= w[1].1), "{:?}", counts);
+        assert!(counts[0].0 < counts[10].0, "{:?}", counts);
+    }
+
+    #[test]
+    fn internal_gaps_describe_uneven_spacing() {
+        let cluster: Anomaly = Anomaly::new(&[10, 11, 15, 16, 22]);
+        assert_eq!((cluster.internal_gap_min, cluster.internal_gap_max), (Some(1), Some(6)));
+        assert_eq!(cluster.internal_gap_mean, Some(3.0));
+        let single: Anomaly = Anomaly::new(&[10]);
+        assert_eq!((single.internal_gap_min, single.internal_gap_max, single.internal_gap_mean), (None, None, None));
+        let gap: Anomaly = Anomaly::gap(22, 100);
+        assert_eq!((gap.internal_gap_min, gap.internal_gap_max, gap.internal_gap_mean), (None, None, None));
+    }
+
+    #[test]
+    fn unsorted_datasets_name_the_first_value_out_of_order() {
+        assert_eq!(Lyagushka::new(vec![1, 2, 2, 5]).unwrap().check_sorted(), Ok(()));
+        assert_eq!(Lyagushka::new(vec![1, 2, 5, 3, 4, 0]).unwrap().check_sorted(), Err(Error::Unsorted { index: 3 }));
+    }
+
+    #[test]
+    fn distances_are_those_between_consecutive_sorted_points() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![10, 1, 4, 4, 20]).unwrap();
+        let distances: Vec<Value> = zhaba.distances();
+        assert_eq!(distances.len(), zhaba.dataset().len() - 1);
+        assert_eq!(distances, [3, 0, 6, 10]);
+        assert!(Lyagushka::empty().distances().is_empty());
+    }
+
+    #[test]
+    fn empty_instances_find_nothing() {
+        for mut zhaba in [Lyagushka::default(), Lyagushka::empty()] {
+            assert!(zhaba.dataset().is_empty());
+            assert_eq!(serde_json::from_str::<Vec<Anomaly>>(&zhaba.search(1.5, 2)).unwrap().len(), 0);
+            assert!(zhaba.anomalies().is_empty());
+        }
+    }
+
+    #[test]
+    fn span_weighting_sets_large_sparse_clusters_apart() {
+        let mut dataset: Vec<Value> = Vec::new();
+        for start in [0, 100, 200] {
+            dataset.extend(start..start + 10);
+        }
+        dataset.extend((400..460).step_by(3));
+        let scores = |density_weight: Option<f32>| -> Vec<f32> {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 5);
+            config.density_weight = density_weight;
+            clusters(dataset.clone(), &config).iter().map(|info: &Anomaly| info.z_score.unwrap()).collect()
+        };
+        let (density, blended): (Vec<f32>, Vec<f32>) = (scores(None), scores(Some(0.8)));
+        // The wide span of the sparse cluster pulls its score up from its low density.
+        assert!(density[3] < -1.5, "{:?}", density);
+        assert!(blended[3] > density[3] + 0.5, "{:?}", blended);
+        assert!(blended[0] < density[0], "{:?}", blended);
+        assert_eq!(scores(Some(1.0)), density);
+    }
+
+    #[test]
+    fn identical_values_form_one_average_cluster() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![7, 7, 7, 7]).unwrap();
+        let output: String = zhaba.search(1.5, 2);
+        assert_eq!(zhaba.anomalies().len(), 1);
+        let cluster: &Anomaly = &zhaba.anomalies()[0];
+        assert_eq!((cluster.start, cluster.end, cluster.num_elements, cluster.z_score), (7, 7, 4, Some(0.0)));
+        assert!(cluster.centroid.is_finite());
+        assert!(!output.contains("null") && !output.contains("NaN"), "{}", output);
+        let summary: &DatasetSummary = zhaba.summary();
+        assert!(summary.mean_distance.is_finite() && summary.cluster_threshold.is_finite() && summary.gap_threshold.is_finite());
+    }
+
+    #[test]
+    fn batches_match_analyzing_every_series() {
+        let datasets: Vec<Vec<Value>> = vec![vec![1, 2, 3, 50, 51, 52], vec![], vec![7, 7, 7], vec![5, 1, 90, 3, 2, 88, 89]];
+        let single: Vec<String> = datasets.iter()
+            .map(|dataset: &Vec<Value>| Lyagushka::new(dataset.clone()).unwrap().search(1.0, 2))
+            .collect();
+        assert_eq!(analyze_batch(datasets, 1.0, 2).unwrap(), single);
+        assert_eq!(analyze_batch(vec![vec![1], vec![Value::MIN, Value::MAX]], 1.0, 2), Err(Error::RangeOverflow { min: Value::MIN, max: Value::MAX }));
+    }
+
+    #[test]
+    fn a_supplied_baseline_replaces_the_dataset_statistics() {
+        let dataset: Vec<Value> = vec![1, 2, 3, 4, 50, 52, 54, 56, 200];
+        let config: SearchConfig = SearchConfig::new(1.5, 3);
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+        zhaba.analyze(&config);
+        let own: Vec<Anomaly> = zhaba.anomalies().to_vec();
+
+        // Scoring against the dataset's own statistics reproduces the scores.
+        let mut same: SearchConfig = config.clone();
+        same.baseline = Some(zhaba.baseline());
+        zhaba.analyze(&same);
+        assert!(zhaba.anomalies().iter().zip(&own).all(|(a, b): (&Anomaly, &Anomaly)| a.approx_eq(b, 1e-6)));
+
+        let mut fixed: SearchConfig = config.clone();
+        fixed.baseline = Some(Baseline::new(0.5, 0.25, 0.0, 10.0));
+        zhaba.analyze(&fixed);
+        assert_eq!(zhaba.baseline(), Baseline::new(0.5, 0.25, 0.0, 10.0));
+        for (info, before) in zhaba.anomalies().iter().zip(&own) {
+            match info.kind() {
+                AnomalyKind::Cluster => {
+                    let density: f32 = info.num_elements as f32 / info.span_length as f32;
+                    assert!((info.z_score.unwrap() - (density - 0.5) / 0.25).abs() < 1e-5, "{}", info);
+                }
+                AnomalyKind::Gap => assert!((info.z_score.unwrap() + info.span_length as f32 / 10.0).abs() < 1e-5, "{}", info),
+            }
+            assert_ne!(info.z_score, before.z_score);
+        }
+    }
+
+    #[test]
+    fn only_points_in_range_are_analyzed() {
+        let mut dataset: Vec<Value> = vec![-5000, -4000];
+        dataset.extend([100, 101, 102, 103, 150, 170, 200, 201, 202, 203]);
+        dataset.extend([9000, 9500, 10000]);
+        let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+        config.range = Some((100, 203));
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+        zhaba.analyze(&config);
+        assert!(zhaba.anomalies().iter().all(|info: &Anomaly| info.start >= 100 && info.end <= 203));
+        assert_eq!(zhaba.summary().num_points, 10);
+
+        // The thresholds are those of the points in range alone.
+        let mut restricted: Lyagushka = Lyagushka::new(dataset[2..12].to_vec()).unwrap();
+        restricted.analyze(&SearchConfig::new(1.5, 3));
+        assert_eq!(zhaba.summary().mean_distance, restricted.summary().mean_distance);
+        assert!(zhaba.anomalies().iter().zip(restricted.anomalies()).all(|(a, b): (&Anomaly, &Anomaly)| a.approx_eq(b, 0.0)));
+        // The full dataset is kept for later analyses.
+        assert_eq!(zhaba.dataset().len(), dataset.len());
+    }
+
+    #[test]
+    fn bundles_hold_the_dataset_parameters_and_anomalies() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![52, 3, 50, 1, 51, 2]).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.0, 2));
+        let bundle: serde_json::Value = serde_json::from_str(&zhaba.to_bundle()).unwrap();
+        assert_eq!(bundle["algorithm_version"], ALGORITHM_VERSION);
+        assert_eq!(bundle["dataset"], serde_json::json!([1, 2, 3, 50, 51, 52]));
+        assert_eq!(bundle["parameters"]["factor"], 1.0);
+        assert_eq!(bundle["parameters"]["min_cluster_size"], 2);
+        assert_eq!(bundle["anomalies"], serde_json::from_str::<serde_json::Value>(&zhaba.to_json()).unwrap());
+        assert_eq!(bundle["anomalies"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn positive_gap_scores_flip_the_sign_of_gaps_only() {
+        let dataset: Vec<Value> = vec![1, 2, 3, 4, 50, 52, 54, 56, 200];
+        let scores = |positive_gap_scores: bool| -> Vec<(AnomalyKind, f32)> {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+            config.positive_gap_scores = positive_gap_scores;
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+            zhaba.analyze(&config);
+            zhaba.anomalies().iter().map(|info: &Anomaly| (info.kind(), info.z_score.unwrap())).collect()
+        };
+        let (negative, positive) = (scores(false), scores(true));
+        assert!(negative.iter().any(|&(kind, _)| kind == AnomalyKind::Gap));
+        for (&(kind, before), &(_, after)) in negative.iter().zip(&positive) {
+            match kind {
+                AnomalyKind::Cluster => assert_eq!(after, before),
+                AnomalyKind::Gap => assert!(before < 0.0 && after == -before, "{} {}", before, after),
+            }
+        }
+    }
+
+    #[test]
+    fn grouped_digits_parse_on_request() {
+        let text: &str = "1,000\n2,000\n10,000";
+        let grouped: ParseOptions = ParseOptions { grouped: true, strict: true, ..ParseOptions::default() };
+        assert_eq!(Lyagushka::parse_with(text, &grouped).unwrap().dataset(), [1000, 2000, 10000]);
+        assert_eq!(Lyagushka::parse_with("1_000\n2 500\n", &grouped).unwrap().dataset(), [1000, 2500]);
+        assert_eq!(Lyagushka::parse(text, true).err(), Some(Error::InvalidLine { line: 1, content: "1,000".to_string() }));
+    }
+
+    #[test]
+    fn clusters_at_the_wrong_spacing_deviate() {
+        let mut dataset: Vec<Value> = (0..20).step_by(2).collect();
+        dataset.extend((300..330).step_by(3));
+        dataset.extend(1000..1010);
+        let mut config: SearchConfig = SearchConfig::new(1.5, 5);
+        config.expected_spacing = Some(2.0);
+        let deviations: Vec<Option<f32>> = clusters(dataset.clone(), &config).iter().map(|info: &Anomaly| info.spacing_deviation).collect();
+        assert_eq!(deviations, [Some(0.0), Some(0.5), Some(-0.5)]);
+        config.expected_spacing = None;
+        assert!(clusters(dataset, &config).iter().all(|info: &Anomaly| info.spacing_deviation.is_none()));
+    }
+
+    #[test]
+    fn periodic_data_reveals_its_period() {
+        let period = |dataset: Vec<Value>, periodicity: bool| -> Option<f32> {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 2);
+            config.periodicity = periodicity;
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+            zhaba.analyze(&config);
+            zhaba.summary().detected_period
+        };
+        assert_eq!(period(vec![0, 10, 20, 30, 40, 50], true), Some(10.0));
+        assert_eq!(period(vec![0, 10, 20, 30, 40, 50], false), None);
+        // Triples of points repeat every 20.
+        let triples: Vec<Value> = (0..8).flat_map(|i: Value| [20 * i, 20 * i + 1, 20 * i + 3]).collect();
+        assert_eq!(period(triples, true), Some(20.0));
+        assert_eq!(period(vec![0, 1, 5, 6, 40, 41, 43, 90, 200, 300, 301], true), None);
+    }
+
+    #[test]
+    fn tight_clusters_score_higher_than_loose_ones() {
+        let even: Anomaly = Anomaly::new(&[0, 2, 4, 6, 8, 10]);
+        let loose: Anomaly = Anomaly::new(&[0, 1, 2, 9, 10, 30]);
+        let (even, loose): (f32, f32) = (even.tightness.unwrap(), loose.tightness.unwrap());
+        assert!((even - 1.0).abs() < 1e-6, "{}", even);
+        assert!(loose < 0.7 && loose > 0.0, "{}", loose);
+        assert_eq!(Anomaly::new(&[5]).tightness, None);
+        assert_eq!(Anomaly::new(&[5, 5, 5]).tightness, Some(1.0));
+        assert_eq!(Anomaly::gap(0, 10).tightness, None);
+    }
+
+    #[test]
+    fn z_scores_beyond_the_cap_are_clamped() {
+        let mut dataset: Vec<Value> = (0..100).map(|i: Value| i * 10).collect();
+        dataset.extend([5000, 5001, 5002, 5003]);
+        let scores = |clamp_z: Option<f32>| -> Vec<f32> {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+            config.clamp_z = clamp_z;
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+            zhaba.analyze(&config);
+            zhaba.anomalies().iter().filter_map(|info: &Anomaly| info.z_score).collect()
+        };
+        let (raw, clamped): (Vec<f32>, Vec<f32>) = (scores(None), scores(Some(0.5)));
+        assert!(raw.iter().any(|z: &f32| *z > 0.5) && raw.iter().any(|z: &f32| *z < -0.5), "{:?}", raw);
+        for (raw, clamped) in raw.iter().zip(&clamped) {
+            assert_eq!(*clamped, raw.clamp(-0.5, 0.5));
+        }
+    }
+
+    #[test]
+    fn cluster_only_and_gap_only_datasets_score_cleanly() {
+        let finite_scores = |zhaba: &Lyagushka| zhaba.anomalies().iter().all(|info: &Anomaly| info.z_score.is_some_and(f32::is_finite));
+
+        // No pair reaches the minimum cluster size, so there are only gaps.
+        let mut gaps_only: Lyagushka = Lyagushka::new(vec![0, 1, 100, 101, 300, 301, 700]).unwrap();
+        gaps_only.analyze(&SearchConfig::new(1.5, 5));
+        assert_eq!((gaps_only.summary().num_clusters, gaps_only.summary().num_gaps), (0, 2));
+        assert!(finite_scores(&gaps_only));
+        assert!(!gaps_only.to_json().contains("null"), "{}", gaps_only.to_json());
+
+        // Evenly spaced points form a single cluster without any gaps.
+        let mut cluster_only: Lyagushka = Lyagushka::new((0..10).collect()).unwrap();
+        cluster_only.analyze(&SearchConfig::new(1.0, 5));
+        assert_eq!((cluster_only.summary().num_clusters, cluster_only.summary().num_gaps), (1, 0));
+        assert_eq!(cluster_only.anomalies()[0].z_score, Some(0.0));
+        assert!(cluster_only.summary().std_dev_inter_cluster_gap.is_none());
+    }
+
+    #[test]
+    fn rounded_centroids_are_integers_in_the_output() {
+        let centroids = |rounding: Option<CentroidRounding>| -> Vec<serde_json::Value> {
+            let mut config: SearchConfig = SearchConfig::new(1.0, 2);
+            config.round_centroid = rounding;
+            let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 4, 50, 51, 52]).unwrap();
+            let output: serde_json::Value = serde_json::from_str(&zhaba.search_with(&config)).unwrap();
+            output.as_array().unwrap().iter().map(|info: &serde_json::Value| info["centroid"].clone()).collect()
+        };
+        assert_eq!(centroids(None), [serde_json::json!(2.5), serde_json::json!(27.0), serde_json::json!(51.0)]);
+        assert_eq!(centroids(Some(CentroidRounding::Nearest)), [serde_json::json!(3), serde_json::json!(27), serde_json::json!(51)]);
+        assert_eq!(centroids(Some(CentroidRounding::Floor)), [serde_json::json!(2), serde_json::json!(27), serde_json::json!(51)]);
+        assert_eq!(centroids(Some(CentroidRounding::Ceil)), [serde_json::json!(3), serde_json::json!(27), serde_json::json!(51)]);
+    }
+
+    #[test]
+    fn every_score_method_scores_the_same_clusters_and_gaps() {
+        let mut dataset: Vec<Value> = Vec::new();
+        for (i, spacing) in [1, 2, 3, 4, 8].into_iter().enumerate() {
+            dataset.extend((0..6).map(|point: Value| 1000 * i as Value + spacing * point));
+        }
+        let analyze = |score_method: ScoreMethod| -> (Vec<Anomaly>, Vec<Anomaly>) {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 4);
+            config.score_method = score_method;
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+            zhaba.analyze(&config);
+            zhaba.anomalies().iter().cloned().partition(|info: &Anomaly| info.kind() == AnomalyKind::Cluster)
+        };
+        let (classic_clusters, classic_gaps): (Vec<Anomaly>, Vec<Anomaly>) = analyze(ScoreMethod::Classic);
+        let densities: Vec<f32> = classic_clusters.iter().map(|info: &Anomaly| info.num_elements as f32 / info.span_length as f32).collect();
+        let gap_spans: Vec<f32> = classic_gaps.iter().map(|info: &Anomaly| info.span_length as f32).collect();
+        let spans: Vec<f32> = classic_clusters.iter().chain(&classic_gaps).map(|info: &Anomaly| info.span_length as f32).collect();
+        assert_eq!((densities.len(), gap_spans.len()), (5, 4));
+        let expected = |values: &[f32], location: f32, scale: f32| -> Vec<f32> {
+            values.iter().map(|value: &f32| (value - location) / scale).collect()
+        };
+        let negated = |scores: Vec<f32>| -> Vec<f32> { scores.into_iter().map(|z: f32| -z).collect() };
+        let scores = |anomalies: &[Anomaly]| -> Vec<f32> { anomalies.iter().map(|info: &Anomaly| info.z_score.unwrap()).collect() };
+        let close = |a: &[f32], b: &[f32]| a.len() == b.len() && a.iter().zip(b).all(|(a, b): (&f32, &f32)| (a - b).abs() < 1e-4);
+
+        // Classic gap scores are the span over the spread of all anomaly spans, negated.
+        let (mean, std_dev): (f32, f32) = mean_and_std_dev(&densities);
+        assert!(close(&scores(&classic_clusters), &expected(&densities, mean, std_dev)), "{:?}", scores(&classic_clusters));
+        let std_dev_span: f32 = mean_and_std_dev(&spans).1;
+        assert!(close(&scores(&classic_gaps), &negated(expected(&gap_spans, 0.0, std_dev_span))), "{:?}", scores(&classic_gaps));
+
+        // The robust methods center gap spans on the median of all anomaly spans.
+        let (median, mad): (f32, f32) = median_and_mad(&densities);
+        let (median_span, mad_span): (f32, f32) = median_and_mad(&spans);
+        assert!(mad > 0.0 && mad_span > 0.0);
+        let (robust_clusters, robust_gaps): (Vec<Anomaly>, Vec<Anomaly>) = analyze(ScoreMethod::Robust);
+        assert!(close(&scores(&robust_clusters), &expected(&densities, median, 1.4826 * mad)), "{:?}", scores(&robust_clusters));
+        assert!(close(&scores(&robust_gaps), &negated(expected(&gap_spans, median_span, 1.4826 * mad_span))), "{:?}", scores(&robust_gaps));
+        let (modified_clusters, modified_gaps): (Vec<Anomaly>, Vec<Anomaly>) = analyze(ScoreMethod::Modified);
+        assert!(close(&scores(&modified_clusters), &expected(&densities, median, mad / 0.6745)), "{:?}", scores(&modified_clusters));
+        assert!(close(&scores(&modified_gaps), &negated(expected(&gap_spans, median_span, mad_span / 0.6745))), "{:?}", scores(&modified_gaps));
+
+        let (rank_clusters, rank_gaps): (Vec<Anomaly>, Vec<Anomaly>) = analyze(ScoreMethod::Rank);
+        assert!(rank_clusters.iter().chain(&rank_gaps).all(|info: &Anomaly| info.z_score.is_none()));
+        let rank_scores = |anomalies: &[Anomaly]| -> Vec<f32> { anomalies.iter().map(|info: &Anomaly| info.rank_score.unwrap()).collect() };
+        assert_eq!(rank_scores(&rank_clusters), [0.9, 0.7, 0.5, 0.3, 0.1]);
+        assert_eq!(rank_scores(&rank_gaps), [0.875, 0.625, 0.375, 0.125]);
+    }
+
+    #[test]
+    fn the_widest_gap_ranks_near_the_top() {
+        let mut dataset: Vec<Value> = Vec::new();
+        let mut start: Value = 0;
+        for gap in [40, 60, 50, 300, 45, 55] {
+            dataset.extend(start..start + 5);
+            start += 4 + gap;
+        }
+        dataset.extend(start..start + 5);
+        let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+        config.score_method = ScoreMethod::Rank;
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        zhaba.analyze(&config);
+        let gaps: Vec<&Anomaly> = zhaba.anomalies().iter().filter(|info: &&Anomaly| info.kind() == AnomalyKind::Gap).collect();
+        assert_eq!(gaps.len(), 6);
+        let widest: &Anomaly = gaps.iter().max_by_key(|info: &&&Anomaly| info.span_length).unwrap();
+        assert_eq!(widest.span_length, 300);
+        assert!(widest.rank_score.unwrap() > 0.9, "{:?}", widest.rank_score);
+        assert!(gaps.iter().all(|info: &&Anomaly| info.rank_score.is_some_and(|score: f32| (0.0..=1.0).contains(&score))));
+        assert!(gaps.iter().all(|info: &&Anomaly| info.z_score.is_none()));
+    }
+
+    #[test]
+    fn spans_render_as_durations() {
+        assert_eq!(TimeUnit::Seconds.format_duration(3600), "1h");
+        assert_eq!(TimeUnit::Seconds.format_duration(5400), "1h 30m");
+        assert_eq!(TimeUnit::Milliseconds.format_duration(90_061_001), "1d 1h 1m 1s 1ms");
+        assert_eq!(TimeUnit::Minutes.format_duration(0), "0s");
+
+        let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+        config.time_unit = Some(TimeUnit::Seconds);
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![0, 1, 2, 3, 3603, 3604, 3605, 3606]).unwrap();
+        zhaba.analyze(&config);
+        let gap: &Anomaly = zhaba.anomalies().iter().find(|info: &&Anomaly| info.kind() == AnomalyKind::Gap).unwrap();
+        assert_eq!((gap.span_length, gap.duration.as_deref()), (3600, Some("1h")));
+        assert_eq!(zhaba.anomalies()[0].duration.as_deref(), Some("3s"));
+    }
+
+    #[test]
+    fn masking_a_known_empty_region_removes_its_gap() {
+        let mut dataset: Vec<Value> = (0..10).collect();
+        dataset.extend(100..110);
+        dataset.extend(1000..1010);
+        dataset.extend(1100..1110);
+        let gaps = |masks: Vec<(Value, Value)>| -> Vec<(Value, Value)> {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+            config.masks = masks;
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+            zhaba.analyze(&config);
+            zhaba.anomalies().iter().filter(|info: &&Anomaly| info.kind() == AnomalyKind::Gap).map(|info: &Anomaly| (info.start, info.end)).collect()
+        };
+        assert_eq!(gaps(Vec::new()), [(9, 100), (109, 1000), (1009, 1100)]);
+        assert_eq!(gaps(vec![(200, 900)]), [(9, 100), (1009, 1100)]);
+        assert_eq!(gaps(vec![(200, 900), (20, 30)]), [(1009, 1100)]);
+    }
+
+    #[test]
+    fn irregular_bins_count_their_points() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 4, 5, 12, 30, 31, 32, 33, 60, 99, 100, 150]).unwrap();
+        // Unsorted and duplicate edges; 150 lies outside the last edge.
+        let bins: Vec<BinCount> = zhaba.bin_counts(&SearchConfig::new(2.0, 3), &[0, 10, 100, 10, 5, 40]);
+        let counts: Vec<(Value, Value, usize)> = bins.iter().map(|bin: &BinCount| (bin.start, bin.end, bin.count)).collect();
+        assert_eq!(counts, [(0, 5, 4), (5, 10, 1), (10, 40, 5), (40, 100, 3)]);
+        assert_eq!(bins[0].density, 0.8);
+        assert_eq!(bins.iter().map(|bin: &BinCount| bin.kind).collect::<Vec<_>>(), [Some(BinKind::Dense), None, None, Some(BinKind::Sparse)]);
+    }
+
+    #[test]
+    fn range_queries_count_the_points_within_closed_ranges() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![9, 1, 5, 5, 3, 7]).unwrap();
+        zhaba.search(1.0, 2);
+        assert_eq!(zhaba.count_in_range(Value::MIN, Value::MAX), 6);
+        assert_eq!(zhaba.count_in_range(1, 9), 6);
+        assert_eq!(zhaba.points_in_range(1, 9), [1, 3, 5, 5, 7, 9]);
+        // Both bounds are included.
+        assert_eq!(zhaba.points_in_range(3, 7), [3, 5, 5, 7]);
+        assert_eq!(zhaba.count_in_range(5, 5), 2);
+        // Ranges without any points, and inverted ones, are empty.
+        assert_eq!(zhaba.count_in_range(10, 20), 0);
+        assert_eq!(zhaba.count_in_range(-5, 0), 0);
+        assert_eq!(zhaba.count_in_range(6, 6), 0);
+        assert!(zhaba.points_in_range(7, 3).is_empty());
+        assert_eq!(Lyagushka::empty().count_in_range(0, 10), 0);
+    }
+
+    #[test]
+    fn profile_peaks_align_with_clusters() {
+        let mut dataset: Vec<Value> = (0..1000).step_by(50).collect();
+        dataset.extend(200..220);
+        dataset.extend(700..740);
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        let profile: Vec<ProfilePoint> = zhaba.density_profile(101);
+        assert_eq!(profile.len(), 101);
+        assert_eq!((profile[0].position, profile[100].position), (0.0, 950.0));
+        let peak: &ProfilePoint = profile.iter().max_by(|a, b| a.density.total_cmp(&b.density)).unwrap();
+        assert!((700.0..=740.0).contains(&peak.position), "{}", peak.position);
+        // The smaller cluster is a local peak of its own.
+        let near = |lo: f32, hi: f32| -> f32 {
+            profile.iter()
+                .filter(|point: &&ProfilePoint| (lo..=hi).contains(&point.position))
+                .map(|point: &ProfilePoint| point.density)
+                .fold(0.0, f32::max)
+        };
+        assert!(near(195.0, 225.0) > 2.0 * near(400.0, 600.0));
+        assert_eq!(zhaba.density_profile(1).len(), 1);
+        assert!(zhaba.density_profile(0).is_empty());
+        assert!(Lyagushka::empty().density_profile(10).is_empty());
+    }
+
+    #[test]
+    fn gap_interiors_exclude_the_bounding_points() {
+        let dataset: Vec<Value> = vec![1, 2, 3, 10, 11, 12];
+        let mut config: SearchConfig = SearchConfig::new(1.0, 2);
+        config.gap_interiors = true;
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        zhaba.analyze(&config);
+        let gap: &Anomaly = &zhaba.anomalies()[1];
+        // `start` and `end` are the data points 3 and 10 on either side of the gap, which also
+        // belong to the clusters; the empty interior holds the values 4 to 9.
+        assert_eq!((gap.start, gap.end), (3, 10));
+        assert_eq!(zhaba.count_in_range(gap.start, gap.end), 2);
+        assert_eq!((gap.empty_start, gap.empty_end), (Some(4), Some(9)));
+        assert_eq!(zhaba.count_in_range(gap.empty_start.unwrap(), gap.empty_end.unwrap()), 0);
+        assert!(zhaba.anomalies().iter()
+            .filter(|info: &&Anomaly| info.kind() == AnomalyKind::Cluster)
+            .all(|info: &Anomaly| info.empty_start.is_none()));
+
+        // Adjacent points have no interior to report.
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![0, 0, 0, 0, 1, 1, 1, 1]).unwrap();
+        zhaba.analyze(&config);
+        let gap: &Anomaly = &zhaba.anomalies()[1];
+        assert_eq!((gap.kind(), gap.span_length, gap.empty_start, gap.empty_end), (AnomalyKind::Gap, 1, None, None));
+
+        config.gap_interiors = false;
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 10, 11, 12]).unwrap();
+        zhaba.analyze(&config);
+        assert_eq!(zhaba.anomalies()[1].empty_start, None);
+    }
+
+    #[test]
+    fn pretty_output_uses_the_configured_indent() {
+        let mut config: SearchConfig = SearchConfig::new(1.0, 2);
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50, 51, 52]).unwrap();
+        assert!(zhaba.search_with(&config).starts_with("[\n  {\n    \"elements\": [\n      1,"));
+        config.indent = Some("    ".to_string());
+        let output: String = zhaba.search_with(&config);
+        assert!(output.starts_with("[\n    {\n        \"elements\": [\n            1,"), "{}", output);
+        let mut streamed: Vec<u8> = Vec::new();
+        zhaba.write_json(&mut streamed).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), output);
+        config.indent = Some("\t".to_string());
+        assert!(zhaba.search_with(&config).starts_with("[\n\t{\n\t\t\"elements\""));
+    }
+
+    #[test]
+    fn evenly_spaced_clusters_have_a_constant_inter_cluster_gap() {
+        let dataset: Vec<Value> = (0..5).flat_map(|i: Value| 100 * i..100 * i + 5).collect();
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.5, 3));
+        assert_eq!(zhaba.summary().num_clusters, 5);
+        assert_eq!(zhaba.summary().mean_inter_cluster_gap, Some(100.0));
+        assert_eq!(zhaba.summary().std_dev_inter_cluster_gap, Some(0.0));
+
+        // A single cluster has no spacing to its neighbours.
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![0, 1, 2, 3, 100]).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.5, 3));
+        assert_eq!(zhaba.summary().num_clusters, 1);
+        assert_eq!((zhaba.summary().mean_inter_cluster_gap, zhaba.summary().std_dev_inter_cluster_gap), (None, None));
+    }
+
+    #[test]
+    #[cfg(feature = "arrow")]
+    fn arrow_output_reads_back() {
+        use arrow_array::{Array, Float32Array, RecordBatch, UInt64Array};
+        use arrow_ipc::reader::StreamReader;
+
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50, 51, 52]).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.0, 2));
+        let bytes: Vec<u8> = zhaba.to_arrow().unwrap();
+        let batches: Vec<RecordBatch> = StreamReader::try_new(bytes.as_slice(), None).unwrap()
+            .collect::<Result<Vec<RecordBatch>, _>>()
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        let batch: &RecordBatch = &batches[0];
+        assert_eq!(batch.num_rows(), zhaba.anomalies().len());
+        let start: &ValueArray = batch.column_by_name("start").unwrap().as_any().downcast_ref::<ValueArray>().unwrap();
+        assert_eq!(start.values().to_vec(), [1, 3, 50]);
+        let num_elements: &UInt64Array = batch.column_by_name("num_elements").unwrap().as_any().downcast_ref::<UInt64Array>().unwrap();
+        assert_eq!(num_elements.values().to_vec(), [3, 0, 3]);
+        let z_score: &Float32Array = batch.column_by_name("z_score").unwrap().as_any().downcast_ref::<Float32Array>().unwrap();
+        assert_eq!(z_score.value(1), zhaba.anomalies()[1].z_score.unwrap());
+        assert!(batch.column_by_name("gap_center").unwrap().is_null(0));
+    }
+
+    #[test]
+    fn diffs_find_added_removed_and_changed_anomalies() {
+        let analyze = |ranges: &[(Value, Value)]| -> Vec<Anomaly> {
+            let dataset: Vec<Value> = ranges.iter().flat_map(|&(lo, hi): &(Value, Value)| lo..hi).collect();
+            let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+            zhaba.analyze(&SearchConfig::new(1.5, 3));
+            zhaba.anomalies().to_vec()
+        };
+        // The last cluster disappears, a new one appears far beyond it and the middle one grows.
+        let previous: Vec<Anomaly> = analyze(&[(0, 10), (100, 110), (200, 210)]);
+        let current: Vec<Anomaly> = analyze(&[(0, 10), (100, 112), (300, 310)]);
+        let changes: AnomalyDiff = diff(previous, current);
+        let ranges = |anomalies: &[Anomaly]| -> Vec<(Value, Value)> {
+            anomalies.iter().map(|info: &Anomaly| (info.start, info.end)).collect()
+        };
+        assert_eq!(ranges(&changes.removed), [(200, 209)]);
+        assert_eq!(ranges(&changes.added), [(300, 309)]);
+        let changed: Vec<((Value, Value), (Value, Value))> = changes.changed.iter()
+            .map(|(before, after): &(Anomaly, Anomaly)| ((before.start, before.end), (after.start, after.end)))
+            .collect();
+        assert_eq!(changed, [((100, 109), (100, 111)), ((109, 200), (111, 300))]);
+
+        let unchanged: AnomalyDiff = diff(analyze(&[(0, 10), (100, 110)]), analyze(&[(0, 10), (100, 110)]));
+        assert!(unchanged.added.is_empty() && unchanged.removed.is_empty() && unchanged.changed.is_empty());
+    }
+
+    #[test]
+    fn clusters_in_expected_dense_regions_are_suppressed() {
+        let mut dataset: Vec<Value> = (0..10).collect();
+        dataset.extend((100..130).step_by(2));
+        dataset.extend(300..305);
+        let analyze = |expected_dense: Vec<(Value, Value)>| -> Vec<Anomaly> {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+            config.expected_dense = expected_dense;
+            clusters(dataset.clone(), &config)
+        };
+        let all: Vec<Anomaly> = analyze(Vec::new());
+        assert_eq!(all.iter().map(|info: &Anomaly| info.start).collect::<Vec<Value>>(), [0, 100, 300]);
+        // Only the cluster fully inside a region is left out; the one reaching beyond stays.
+        let filtered: Vec<Anomaly> = analyze(vec![(-5, 20), (290, 302)]);
+        assert_eq!(filtered.iter().map(|info: &Anomaly| info.start).collect::<Vec<Value>>(), [100, 300]);
+        // The suppressed cluster still counts towards the statistics the others are scored by.
+        assert_eq!(filtered[0].z_score, all[1].z_score);
+        assert_eq!(filtered[1].z_score, all[2].z_score);
+    }
+
+    #[test]
+    fn searches_return_the_anomalies_they_serialized() {
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 4, 50, 52, 54, 56, 200]).unwrap();
+        let (json, anomalies): (String, Vec<Anomaly>) = zhaba.search_with_anomalies(&SearchConfig::new(1.5, 3));
+        let parsed: Vec<Anomaly> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.len(), anomalies.len());
+        assert!(parsed.iter().zip(&anomalies).all(|(a, b): (&Anomaly, &Anomaly)| a.approx_eq(b, 0.0)));
+        assert_eq!(json, zhaba.search(1.5, 3));
+    }
+
+    #[test]
+    fn the_sparse_tail_of_a_cluster_is_its_border() {
+        let mut cluster: Vec<Value> = (0..=10).collect();
+        cluster.extend([12, 14, 16]);
+        // Within 2, the tail points have two or three neighbours, themselves included, as has 0.
+        assert_eq!(count_core_points(&cluster, 2.0, true, 4), 10);
+        assert_eq!(count_core_points(&cluster, 2.0, false, 3), 9);
+
+        let mut dataset: Vec<Value> = cluster.clone();
+        dataset.extend(100..=110);
+        let mut config: SearchConfig = SearchConfig::new(2.0, 4);
+        config.core_points = true;
+        let found: Vec<Anomaly> = clusters(dataset, &config);
+        assert_eq!((found[0].start, found[0].end), (0, 16));
+        assert_eq!((found[0].core_count, found[0].border_count), (Some(10), Some(4)));
+        assert_eq!((found[1].core_count, found[1].border_count), (Some(9), Some(2)));
+    }
+
+    #[test]
+    fn pass_limits_cut_the_stability_sweep_short() {
+        let sweep = |max_passes: Option<usize>, timeout: Option<f32>| -> (Option<usize>, bool, Option<f32>) {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+            config.stability_band = Some(0.2);
+            (config.max_passes, config.timeout) = (max_passes, timeout);
+            let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 4, 50, 52, 54, 56, 200]).unwrap();
+            zhaba.analyze(&config);
+            (zhaba.summary().stability_passes, zhaba.summary().pass_limit_reached, zhaba.anomalies()[0].stability)
+        };
+        assert_eq!(sweep(None, None), (Some(STABILITY_STEPS), false, Some(1.0)));
+        assert_eq!(sweep(Some(100), None), (Some(STABILITY_STEPS), false, Some(1.0)));
+        assert_eq!(sweep(Some(2), None), (Some(2), true, Some(1.0)));
+        // A timeout that has already passed allows no scan at all.
+        assert_eq!(sweep(None, Some(0.0)), (Some(0), true, None));
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50]).unwrap();
+        zhaba.analyze(&SearchConfig::new(1.5, 3));
+        assert_eq!((zhaba.summary().stability_passes, zhaba.summary().pass_limit_reached), (None, false));
+    }
+
+    #[test]
+    fn gap_centers_lie_between_the_neighbouring_clusters() {
+        let mut dataset: Vec<Value> = (0..10).collect();
+        dataset.push(40);
+        dataset.extend(200..210);
+        let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+        config.gap_centers = true;
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        zhaba.analyze(&config);
+        let gaps: Vec<(Value, Value, f32, Option<f32>)> = zhaba.anomalies().iter()
+            .filter(|info: &&Anomaly| info.kind() == AnomalyKind::Gap)
+            .map(|info: &Anomaly| (info.start, info.end, info.centroid, info.gap_center))
+            .collect();
+        // The outlier at 40 bounds both gaps, but the centers lie between the clusters around them.
+        assert_eq!(gaps, [(9, 40, 24.5, Some(104.5)), (40, 200, 120.0, Some(104.5))]);
+
+        config.gap_centers = false;
+        zhaba.analyze(&config);
+        assert!(zhaba.anomalies().iter().all(|info: &Anomaly| info.gap_center.is_none()));
+    }
+
+    #[test]
+    fn nonfinite_values_are_an_error_or_dropped() {
+        let text: &str = "1\n2\nNaN\n3\n50\n-inf\n51\n52\n";
+        let strict: ParseOptions = ParseOptions { strict: true, ..ParseOptions::default() };
+        assert_eq!(Lyagushka::parse_with(text, &strict).err(), Some(Error::NonFinite { lines: vec![3, 6] }));
+
+        let dropping: ParseOptions = ParseOptions { on_nonfinite: NonFinitePolicy::Drop, ..ParseOptions::default() };
+        let mut zhaba: Lyagushka = Lyagushka::parse_with(text, &dropping).unwrap();
+        assert_eq!(zhaba.dataset(), [1, 2, 3, 50, 51, 52]);
+        let config: SearchConfig = SearchConfig::new(1.5, 3);
+        zhaba.analyze(&config);
+        let mut clean: Lyagushka = Lyagushka::new(vec![1, 2, 3, 50, 51, 52]).unwrap();
+        clean.analyze(&config);
+        assert!(!clean.anomalies().is_empty());
+        assert_eq!(zhaba.to_json(), clean.to_json());
+    }
+
+    #[test]
+    fn corrections_flag_fewer_anomalies_than_the_uncorrected_level() {
+        // Irregular spacings from a linear congruential generator, and one far larger gap.
+        let mut state: u64 = 7;
+        let mut value: Value = 0;
+        let mut dataset: Vec<Value> = Vec::new();
+        for index in 0..3000 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            value += (state >> 59) as Value + 1 + if index == 1500 { 5000 } else { 0 };
+            dataset.push(value);
+        }
+        let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        let mut significant = |correction: Correction| -> (usize, usize) {
+            config.correction = Some(correction);
+            zhaba.analyze(&config);
+            let uncorrected: usize = zhaba.anomalies().iter()
+                .filter(|info: &&Anomaly| info.p_value.is_some_and(|p: f32| p <= config.alpha))
+                .count();
+            let corrected: usize = zhaba.anomalies().iter().filter(|info: &&Anomaly| info.significant == Some(true)).count();
+            (uncorrected, corrected)
+        };
+        let (uncorrected, bonferroni): (usize, usize) = significant(Correction::Bonferroni);
+        let (_, benjamini_hochberg): (usize, usize) = significant(Correction::BenjaminiHochberg);
+        assert!(0 < bonferroni && bonferroni < uncorrected, "{} of {}", bonferroni, uncorrected);
+        assert!(bonferroni <= benjamini_hochberg && benjamini_hochberg < uncorrected, "{} of {}", benjamini_hochberg, uncorrected);
+        // The planted gap survives either correction.
+        let widest: &Anomaly = zhaba.anomalies().iter().find(|info: &&Anomaly| info.end - info.start > 5000).unwrap();
+        assert_eq!(widest.significant, Some(true));
+    }
+
+    #[test]
+    fn last_summary_is_cached_by_search() {
+        let dataset: Vec<Value> = vec![1, 2, 3, 50, 51, 52, 90];
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset.clone()).unwrap();
+        assert!(zhaba.last_summary().is_none());
+        zhaba.search(1.5, 3);
+        // Through a string, as the report serializes its `f32` fields with their shortest representation.
+        let cached: serde_json::Value = serde_json::from_str(&serde_json::to_string(zhaba.last_summary().unwrap()).unwrap()).unwrap();
+
+        let report: String = Lyagushka::new(dataset).unwrap().search_report(&SearchConfig::new(1.5, 3));
+        let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+        assert_eq!(cached, report["summary"]);
+        assert_eq!(cached["num_points"], 7);
+        assert!(cached["mean_distance"].as_f64().is_some_and(|mean: f64| (mean - 89.0 / 6.0).abs() < 1e-4));
+    }
+
+    #[test]
+    fn two_scale_clusters_nest_tight_groups_in_looser_ones() {
+        // Three super-groups of three tight clusters each, and one lone tight cluster.
+        let mut dataset: Vec<Value> = Vec::new();
+        for group in [0, 5000, 10000, 30000] {
+            let members: Value = if group == 30000 { 1 } else { 3 };
+            for cluster in 0..members {
+                let start: Value = group + cluster * 200;
+                dataset.extend(start..start + 10);
+            }
+        }
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        let nested: TwoScaleClusters = zhaba.two_scale_clusters(&SearchConfig::new(2.0, 5), 1.5);
+        let groups: Vec<(Value, Value, usize, usize)> = nested.groups.iter()
+            .map(|group: &ClusterGroup| (group.start, group.end, group.clusters.len(), group.num_elements))
+            .collect();
+        assert_eq!(groups, [(0, 409, 3, 30), (5000, 5409, 3, 30), (10000, 10409, 3, 30)]);
+        assert!(nested.groups.iter().all(|group: &ClusterGroup| group.clusters.iter().all(|info: &Anomaly| info.num_elements == 10)));
+        let ungrouped: Vec<(Value, Value)> = nested.ungrouped.iter().map(|info: &Anomaly| (info.start, info.end)).collect();
+        assert_eq!(ungrouped, [(30000, 30009)]);
+    }
+
+    #[test]
+    fn ranks_are_a_permutation_per_kind() {
+        let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+        config.rank = true;
+        let dataset: Vec<Value> = generated(30, 12, 25, 120, 5);
+        let mut zhaba: Lyagushka = Lyagushka::new(dataset).unwrap();
+        zhaba.analyze(&config);
+        for kind in [AnomalyKind::Cluster, AnomalyKind::Gap] {
+            let of_kind: Vec<&Anomaly> = zhaba.anomalies().iter()
+                .filter(|info: &&Anomaly| info.kind() == kind && !info.dead_zone)
+                .collect();
+            assert!(of_kind.len() > 1, "{:?}", kind);
+            let mut ranks: Vec<usize> = of_kind.iter().map(|info: &&Anomaly| info.rank.unwrap()).collect();
+            ranks.sort_unstable();
+            assert_eq!(ranks, (1..=of_kind.len()).collect::<Vec<usize>>(), "{:?}", kind);
+            // The first rank goes to the most extreme score.
+            let first: &Anomaly = of_kind.iter().find(|info: &&&Anomaly| info.rank == Some(1)).unwrap();
+            assert!(of_kind.iter().all(|info: &&Anomaly| info.z_score.unwrap().abs() <= first.z_score.unwrap().abs()));
+        }
+
+        // Equal scores are ranked in ascending order of position.
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![0, 1, 2, 50, 51, 52, 100, 101, 102]).unwrap();
+        zhaba.analyze(&config);
+        let ranks: Vec<Option<usize>> = zhaba.anomalies().iter().map(|info: &Anomaly| info.rank).collect();
+        assert_eq!(ranks, [Some(1), Some(1), Some(2), Some(2), Some(3)]);
+
+        // So are scores equally far from average on either side: the percentiles of two clusters
+        // mirror each other around the median, and the sparser, first one comes first.
+        config.score_method = ScoreMethod::Rank;
+        let mut zhaba: Lyagushka = Lyagushka::new(vec![0, 2, 4, 6, 100, 101, 102, 103]).unwrap();
+        zhaba.analyze(&config);
+        let ranked: Vec<(f32, Option<usize>)> = zhaba.anomalies().iter()
+            .filter(|info: &&Anomaly| info.kind() == AnomalyKind::Cluster)
+            .map(|info: &Anomaly| (info.rank_score.unwrap(), info.rank))
+            .collect();
+        assert_eq!(ranked, [(0.25, Some(1)), (0.75, Some(2))]);
+    }
+
+    #[test]
+    fn strict_parsing_skips_comments_and_blank_lines() {
+        let text: &str = "# sensor readings\n# unit: ms\n1\n2\n\n   \n3\n  # trailing note\n50\n";
+        let strict: ParseOptions = ParseOptions { strict: true, ..ParseOptions::default() };
+        assert_eq!(Lyagushka::parse_with(text, &strict).unwrap().dataset(), [1, 2, 3, 50]);
+
+        let custom: ParseOptions = ParseOptions { strict: true, comment_prefix: Some("%".to_string()), ..ParseOptions::default() };
+        assert_eq!(Lyagushka::parse_with("% header\n\n4\n5\n", &custom).unwrap().dataset(), [4, 5]);
+        // With another prefix, `#` lines are no longer comments.
+        assert_eq!(Lyagushka::parse_with("# header\n4\n", &custom).err(), Some(Error::InvalidLine { line: 1, content: "# header".to_string() }));
+    }
+
+    #[test]
+    fn log_densities_reduce_the_skew_of_wide_ranging_densities() {
+        // Runs of ten points, mostly loose and a few very tight, separated by wide gaps.
+        let mut dataset: Vec<Value> = Vec::new();
+        for (run, spacing) in [8, 8, 8, 8, 7, 6, 4, 2, 1].into_iter().enumerate() {
+            let start: Value = run as Value * 10000;
+            dataset.extend((0..10).map(|point: Value| start + point * spacing));
+        }
+        let cluster_scores = |log_densities: bool| -> Vec<f32> {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 5);
+            config.log_densities = log_densities;
+            clusters(dataset.clone(), &config).iter().filter_map(|info: &Anomaly| info.z_score).collect()
+        };
+        let (raw, log): (Vec<f32>, Vec<f32>) = (cluster_scores(false), cluster_scores(true));
+        assert_eq!((raw.len(), log.len()), (9, 9));
+        assert!(skewness(&log).abs() < skewness(&raw).abs(), "{} vs {}", skewness(&log), skewness(&raw));
+
+        // A cluster of identical points has an infinite density and no logarithm.
+        dataset.extend([200000; 10]);
+        let mut config: SearchConfig = SearchConfig::new(1.5, 5);
+        config.log_densities = true;
+        let scores: Vec<Option<f32>> = clusters(dataset, &config).iter().map(|info: &Anomaly| info.z_score).collect();
+        // It is left unscored and out of the statistics of the others.
+        assert_eq!(scores[9], None);
+        assert_eq!(scores[..9], log.into_iter().map(Some).collect::<Vec<Option<f32>>>());
+    }
+
+    #[test]
+    fn shrinkage_pulls_small_clusters_in_more_than_large_ones() {
+        // Loose background clusters, then a tiny and a large cluster both 0.75 points per unit.
+        let mut dataset: Vec<Value> = Vec::new();
+        for run in 0..4 {
+            dataset.extend((0..10).map(|point: Value| run * 1000 + point * 4));
+        }
+        dataset.extend([5000, 5002, 5004]);
+        // 30 points over 40 units: 18 steps of one and 11 of two.
+        let mut point: Value = 6000;
+        for step in 0..30 {
+            dataset.push(point);
+            point += if step < 18 { 1 } else { 2 };
+        }
+        let scores = |shrinkage: bool| -> Vec<(usize, f32)> {
+            let mut config: SearchConfig = SearchConfig::new(1.5, 3);
+            config.shrinkage = shrinkage;
+            clusters(dataset.clone(), &config).iter().map(|info: &Anomaly| (info.num_elements, info.z_score.unwrap())).collect()
+        };
+        let raw: Vec<(usize, f32)> = scores(false);
+        let shrunk: Vec<(usize, f32)> = scores(true);
+        let [.., (3, raw_tiny), (30, raw_large)] = raw[..] else { panic!("{:?}", raw) };
+        let [.., (3, tiny), (30, large)] = shrunk[..] else { panic!("{:?}", shrunk) };
+        assert_eq!(raw_tiny, raw_large);
+        assert!(0.0 < tiny && tiny < large && large < raw_large, "{} and {}", tiny, large);
+        // Each deviation keeps `1 - 1 / sqrt(n)` of itself.
+        let kept = |n: f32| -> f32 { 1.0 - 1.0 / n.sqrt() };
+        assert!((tiny / large - kept(3.0) / kept(30.0)).abs() < 1e-5);
+    }
+}
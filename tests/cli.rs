@@ -0,0 +1,487 @@
+//! End-to-end tests of the command-line tool.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the tool with `args` and `input` piped into stdin.
+fn run(args: &[&str], input: &str) -> Output {
+    run_with_env(args, &[], input)
+}
+
+/// Runs the tool like [`run`], with the environment variables `vars` set and no other
+/// `LYAGUSHKA_` parameters inherited.
+fn run_with_env(args: &[&str], vars: &[(&str, &str)], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lyagushka"))
+        .args(args)
+        .env_remove("LYAGUSHKA_FACTOR")
+        .env_remove("LYAGUSHKA_MIN_CLUSTER_SIZE")
+        .envs(vars.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("the tool starts");
+    // The tool may reject its arguments and exit before reading the input, closing the pipe.
+    let _ = child.stdin.take().expect("stdin is piped").write_all(input.as_bytes());
+    child.wait_with_output().expect("the tool finishes")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).expect("the output is UTF-8")
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).expect("the error output is UTF-8")
+}
+
+/// The anomalies of a plain JSON output.
+fn anomalies(output: &Output) -> Vec<serde_json::Value> {
+    assert!(output.status.success(), "{}", stderr(output));
+    serde_json::from_str(&stdout(output)).expect("the output is a JSON array")
+}
+
+#[test]
+fn generated_data_analyzes_into_its_clusters() {
+    let generated: Output = run(&["generate", "--clusters", "4", "--points-per-cluster", "20", "--seed", "1"], "");
+    assert!(generated.status.success());
+    let clusters: usize = anomalies(&run(&["1.5", "6"], &stdout(&generated))).iter()
+        .filter(|info: &&serde_json::Value| info["num_elements"].as_u64() > Some(0))
+        .count();
+    assert_eq!(clusters, 4);
+}
+
+#[test]
+fn generate_rejects_negative_and_overflowing_parameters() {
+    for args in [&["generate", "--spread", "-5"][..], &["generate", "--gap", "-1"], &["generate", "--clusters", "3", "--gap", "1000000000"]] {
+        let output: Output = run(args, "");
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert!(output.stdout.is_empty());
+    }
+}
+
+#[test]
+fn out_of_range_options_are_rejected() {
+    for (flag, value) in [("--sample", "0"), ("--sample", "-0.5"), ("--sample", "NaN"), ("--sample", "1.5"), ("--alpha", "1")] {
+        let output: Output = run(&[flag, value, "1.5", "2"], "1\n2\n3\n50\n51\n52\n");
+        assert_eq!(output.status.code(), Some(1), "{} {}", flag, value);
+        assert!(stderr(&output).starts_with(&format!("Invalid value for {}:", flag)), "{}", stderr(&output));
+        assert!(output.stdout.is_empty());
+    }
+    assert!(run(&["--sample", "1", "1.5", "2"], "1\n2\n3\n50\n51\n52\n").status.success());
+}
+
+#[test]
+#[cfg(not(feature = "bigint"))]
+fn overflowing_deltas_are_rejected() {
+    let output: Output = run(&["--mode", "deltas", "2", "2"], "-2147483648\n2147483647\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("too wide"), "{}", stderr(&output));
+}
+
+#[test]
+fn fail_on_anomaly_sets_the_exit_status() {
+    let input: &str = "1\n2\n3\n4\n5\n100\n101\n102\n103\n104\n200\n";
+    let passing: Output = run(&["--fail-on-anomaly", "1000", "1.5", "3"], input);
+    assert_eq!(passing.status.code(), Some(0), "{}", stderr(&passing));
+    let failing: Output = run(&["--fail-on-anomaly", "0", "1.5", "3"], input);
+    assert_eq!(failing.status.code(), Some(2));
+    assert!(!failing.stdout.is_empty(), "the output is printed before the verdict");
+    let clamped: Output = run(&["--fail-on-anomaly", "3", "--clamp-z", "2", "1.5", "3"], input);
+    assert_eq!(clamped.status.code(), Some(1));
+    assert!(clamped.stdout.is_empty());
+}
+
+#[test]
+fn weighted_input_weights_cluster_densities() {
+    let values: &str = "1\n2\n3\n4\n50\n51\n52\n53\n200\n";
+    let unweighted: Vec<serde_json::Value> = anomalies(&run(&["1.5", "3"], values));
+    let weighted: Vec<serde_json::Value> = anomalies(&run(&["--weighted-input", "1.5", "3"], "1 10\n2 10\n3 10\n4 10\n50 1\n51 1\n52 1\n53 1\n200 1\n"));
+    let elements = |anomalies: &[serde_json::Value]| -> Vec<u64> {
+        anomalies.iter().filter_map(|info: &serde_json::Value| info["num_elements"].as_u64()).filter(|&count: &u64| count > 0).collect()
+    };
+    assert_eq!(elements(&unweighted), [4, 4]);
+    assert_eq!(elements(&weighted), [40, 4]);
+    assert!(weighted[0]["z_score"].as_f64() > unweighted[0]["z_score"].as_f64());
+}
+
+#[test]
+fn weighted_input_rejects_grouped_digits() {
+    let output: Output = run(&["--weighted-input", "--parse-grouped", "1.5", "3"], "1 10\n2 10\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("--parse-grouped"));
+}
+
+#[test]
+fn epsilon_merges_nearly_equal_readings() {
+    let input: &str = "1000\n1002\n1001\n1000\n1500\n1503\n1501\n1502\n3000\n";
+    let clusters = |args: &[&str]| -> Vec<(u64, Vec<serde_json::Value>)> {
+        anomalies(&run(args, input)).into_iter()
+            .filter(|info: &serde_json::Value| info["num_elements"].as_u64() > Some(0))
+            .map(|info: serde_json::Value| (info["span_length"].as_u64().unwrap(), info["elements"].as_array().unwrap().clone()))
+            .collect()
+    };
+    assert!(clusters(&["1.5", "3"]).iter().all(|(span, _)| *span > 0));
+    for (span, elements) in clusters(&["--epsilon", "3", "1.5", "3"]) {
+        assert_eq!(span, 0);
+        assert!(elements.windows(2).all(|w: &[serde_json::Value]| w[0] == w[1]));
+    }
+    assert_eq!(run(&["--epsilon", "-1", "1.5", "3"], input).status.code(), Some(1));
+}
+
+/// Writes `contents` to a file named `name` in a directory of its own for `test`, returning its path.
+fn input_file(test: &str, name: &str, contents: &str) -> String {
+    let directory: std::path::PathBuf = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(test);
+    std::fs::create_dir_all(&directory).expect("the directory is created");
+    let path: std::path::PathBuf = directory.join(name);
+    std::fs::write(&path, contents).expect("the file is written");
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn parallel_honors_the_gate_and_the_order_check() {
+    let sorted: String = input_file("parallel", "sorted.txt", "1\n2\n3\n4\n5\n100\n101\n102\n103\n104\n200\n");
+    let unsorted: String = input_file("parallel", "unsorted.txt", "5\n4\n3\n2\n1\n");
+
+    let passing: Output = run(&["--parallel", "2", "--fail-on-anomaly", "1000", &sorted, &sorted, "1.5", "3"], "");
+    assert_eq!(passing.status.code(), Some(0), "{}", stderr(&passing));
+    let failing: Output = run(&["--parallel", "2", "--fail-on-anomaly", "0", &sorted, "1.5", "3"], "");
+    assert_eq!(failing.status.code(), Some(2));
+    assert!(stdout(&failing).lines().all(|line: &str| line.contains("\"source\"")));
+
+    let out_of_order: Output = run(&["--parallel", "2", "--require-sorted", &sorted, &unsorted, "1.5", "3"], "");
+    assert_eq!(out_of_order.status.code(), Some(1));
+    assert!(stderr(&out_of_order).contains("unsorted.txt"));
+}
+
+#[test]
+fn parallel_rejects_other_outputs() {
+    let file: String = input_file("parallel_outputs", "data.txt", "1\n2\n3\n50\n51\n52\n");
+    for flags in [&["--output", "report"][..], &["--emit-distances"], &["--threshold-report", "4"], &["--bin-edges", "0,10"], &["--profile", "5"], &["--two-scale", "3"], &["--explain-params"], &["--units", "ms"]] {
+        let args: Vec<&str> = ["--parallel", "2"].iter().chain(flags).chain(&[file.as_str(), "1.5", "3"]).copied().collect();
+        let output: Output = run(&args, "");
+        assert_eq!(output.status.code(), Some(1), "{:?}", flags);
+        assert!(stderr(&output).contains(flags[0]), "{:?}", flags);
+    }
+    assert!(run(&["--parallel", "2", "--output", "ndjson", &file, "1.5", "3"], "").status.success());
+}
+
+#[test]
+fn skipped_lines_are_reported() {
+    let out_of_range: Output = run(&["1.5", "2"], "1\n2\n3\n99999999999999999999999999999999999999999\n9\n10\n");
+    assert!(out_of_range.status.success());
+    assert!(stderr(&out_of_range).contains("dropped 1 values out of range"), "{}", stderr(&out_of_range));
+
+    let nonfinite: Output = run(&["1.5", "2"], "1\nNaN\n3\n");
+    assert_eq!(nonfinite.status.code(), Some(1));
+    assert!(stderr(&nonfinite).contains("on lines 2; pass --on-nonfinite drop"), "{}", stderr(&nonfinite));
+    let dropped: Output = run(&["--on-nonfinite", "drop", "1.5", "2"], "1\nNaN\n3\n");
+    assert!(dropped.status.success());
+    assert!(stderr(&dropped).contains("dropped 1 non-finite values, the first on line 2"));
+
+    let strict: Output = run(&["--strict", "1.5", "2"], "1\nabc\n");
+    assert_eq!(strict.status.code(), Some(1));
+    assert!(stderr(&strict).contains("Line 2: abc is not a number"));
+}
+
+#[test]
+fn units_imply_the_report_output() {
+    let output: Output = run(&["--units", "ms", "1.0", "2"], "1\n2\n3\n50\n51\n52\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).expect("the output is a JSON report");
+    assert_eq!(report["summary"]["units"], "ms");
+}
+
+#[test]
+fn cluster_elements_can_keep_the_input_order() {
+    let clusters: Vec<serde_json::Value> = anomalies(&run(&["--sorted-output-by-input-order", "1.0", "2"], "52\n3\n50\n1\n100\n2\n51\n"))
+        .into_iter()
+        .filter(|info: &serde_json::Value| info["num_elements"].as_u64() > Some(0))
+        .collect();
+    assert_eq!(clusters[0]["elements"], serde_json::json!([3, 1, 2]));
+    assert_eq!(clusters[1]["indices"], serde_json::json!([0, 2, 6]));
+}
+
+#[test]
+fn discarding_every_cluster_is_a_warning() {
+    let input: &str = "1\n2\n50\n51\n100\n101\n102\n";
+    let discarded: Output = run(&["1.0", "4"], input);
+    assert!(discarded.status.success());
+    assert!(stderr(&discarded).contains("all 3 candidate clusters had fewer than 4 points"), "{}", stderr(&discarded));
+    assert!(stderr(&run(&["1.0", "3"], input)).is_empty());
+}
+
+#[test]
+fn boundaries_are_labeled_in_snake_case() {
+    let boundaries: Vec<serde_json::Value> = anomalies(&run(&["--boundaries", "1.0", "2"], "1\n2\n3\n50\n51\n52\n"));
+    assert_eq!(boundaries.len(), 6);
+    assert_eq!(boundaries[2], serde_json::json!({ "position": 3, "kind": "gap_start" }));
+    assert_eq!(boundaries[3], serde_json::json!({ "position": 50, "kind": "gap_end" }));
+}
+
+#[test]
+fn sensitivity_replaces_the_factor_argument() {
+    let input: &str = "1\n2\n3\n50\n51\n52\n";
+    assert_eq!(anomalies(&run(&["--sensitivity", "1", "2"], input)), anomalies(&run(&["1.0", "2"], input)));
+    assert_eq!(anomalies(&run(&["--sensitivity", "0.5", "2"], input)), anomalies(&run(&["2.0", "2"], input)));
+    assert!(!run(&["--sensitivity", "0.5", "1.0", "2"], input).status.success());
+}
+
+#[test]
+fn require_sorted_rejects_unsorted_input() {
+    let unsorted: Output = run(&["--require-sorted", "1.0", "2"], "1\n2\n5\n3\n");
+    assert_eq!(unsorted.status.code(), Some(1));
+    assert!(stderr(&unsorted).contains("value at index 3 is smaller than its predecessor"), "{}", stderr(&unsorted));
+    assert!(stdout(&unsorted).is_empty());
+    assert_eq!(anomalies(&run(&["--require-sorted", "1.0", "2"], "1\n2\n3\n5\n")), anomalies(&run(&["1.0", "2"], "5\n3\n2\n1\n")));
+}
+
+#[test]
+fn emitted_distances_are_one_per_line() {
+    let output: Output = run(&["--emit-distances", "1.0", "2"], "10\n1\n4\n20\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "3\n6\n10\n");
+}
+
+#[test]
+fn range_limits_the_analysis_to_its_points() {
+    let input: &str = "-5000\n100\n101\n102\n150\n200\n201\n202\n9000\n";
+    let restricted: Vec<serde_json::Value> = anomalies(&run(&["--range", "100:202", "1.5", "3"], input));
+    assert_eq!(restricted, anomalies(&run(&["1.5", "3"], "100\n101\n102\n150\n200\n201\n202\n")));
+    assert!(!run(&["--range", "202:100", "1.5", "3"], input).status.success());
+}
+
+#[test]
+fn bundle_output_holds_the_sorted_dataset() {
+    let output: Output = run(&["--output", "bundle", "1.0", "2"], "3\n1\n2\n50\n52\n51\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let bundle: serde_json::Value = serde_json::from_str(&stdout(&output)).expect("the output is a JSON bundle");
+    assert_eq!(bundle["dataset"], serde_json::json!([1, 2, 3, 50, 51, 52]));
+    assert_eq!(bundle["parameters"]["min_cluster_size"], 2);
+    assert_eq!(bundle["anomalies"].as_array().map(Vec::len), Some(3));
+}
+
+#[test]
+fn gap_sign_selects_the_sign_of_gap_scores() {
+    let input: &str = "1\n2\n3\n4\n50\n52\n54\n56\n200\n";
+    let gap_scores = |args: &[&str]| -> Vec<f64> {
+        anomalies(&run(args, input)).iter()
+            .filter(|info: &&serde_json::Value| info["num_elements"] == 0)
+            .map(|info: &serde_json::Value| info["z_score"].as_f64().expect("gaps are scored"))
+            .collect()
+    };
+    let negative: Vec<f64> = gap_scores(&["1.5", "3"]);
+    assert!(!negative.is_empty() && negative.iter().all(|&z: &f64| z < 0.0));
+    assert_eq!(gap_scores(&["--gap-sign", "negative", "1.5", "3"]), negative);
+    assert_eq!(gap_scores(&["--gap-sign", "positive", "1.5", "3"]), negative.iter().map(|z: &f64| -z).collect::<Vec<f64>>());
+    assert!(!run(&["--gap-sign", "up", "1.5", "3"], input).status.success());
+}
+
+#[test]
+fn out_of_range_values_are_not_dropped_silently() {
+    let input: &str = "1\n99999999999999999999999999999999999999999\n2\n-99999999999999999999999999999999999999999\n3\n";
+    let lenient: Output = run(&["1.0", "2"], input);
+    assert!(lenient.status.success());
+    assert!(stderr(&lenient).contains("dropped 2 values out of range for the value type"), "{}", stderr(&lenient));
+    assert!(stderr(&lenient).contains("the first on line 2"), "{}", stderr(&lenient));
+    assert!(!stderr(&lenient).contains("not a number"), "{}", stderr(&lenient));
+
+    let strict: Output = run(&["--strict", "1.0", "2"], input);
+    assert_eq!(strict.status.code(), Some(1));
+    assert!(stderr(&strict).contains("Line 2: 99999999999999999999999999999999999999999 is out of range"), "{}", stderr(&strict));
+}
+
+#[test]
+fn grouped_digits_parse_with_the_option() {
+    let input: &str = "1,000\n1,001\n1,002\n10,000\n10,001\n10,002\n";
+    let clusters: Vec<serde_json::Value> = anomalies(&run(&["--parse-grouped", "1.0", "2"], input));
+    assert_eq!(clusters[0]["elements"], serde_json::json!([1000, 1001, 1002]));
+    assert_eq!(clusters[2]["elements"], serde_json::json!([10000, 10001, 10002]));
+    let strict: Output = run(&["--strict", "1.0", "2"], input);
+    assert_eq!(strict.status.code(), Some(1));
+    assert!(stderr(&strict).contains("Line 1: 1,000 is not a number"), "{}", stderr(&strict));
+}
+
+#[test]
+fn out_writes_the_output_to_a_file() {
+    let input: &str = "1\n2\n3\n50\n51\n52\n";
+    let path: String = input_file("out_writes_the_output_to_a_file", "anomalies.json", "");
+    let output: Output = run(&["--out", &path, "1.0", "2"], input);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty());
+    let written: String = std::fs::read_to_string(&path).expect("the output file is read");
+    assert_eq!(written, stdout(&run(&["1.0", "2"], input)));
+}
+
+#[test]
+fn parameters_fall_back_to_the_environment() {
+    let input: &str = "1\n2\n3\n50\n51\n52\n";
+    let expected: Vec<serde_json::Value> = anomalies(&run(&["1.0", "2"], input));
+    let both: [(&str, &str); 2] = [("LYAGUSHKA_FACTOR", "1.0"), ("LYAGUSHKA_MIN_CLUSTER_SIZE", "2")];
+    assert_eq!(anomalies(&run_with_env(&[], &both, input)), expected);
+    assert_eq!(anomalies(&run_with_env(&["2"], &both[..1], input)), expected);
+    assert_eq!(anomalies(&run_with_env(&["1.0"], &both[1..], input)), expected);
+    // Command-line parameters take precedence.
+    let overridden: [(&str, &str); 2] = [("LYAGUSHKA_FACTOR", "9.0"), ("LYAGUSHKA_MIN_CLUSTER_SIZE", "50")];
+    assert_eq!(anomalies(&run_with_env(&["1.0", "2"], &overridden, input)), expected);
+
+    let missing: Output = run_with_env(&[], &both[..1], input);
+    assert_eq!(missing.status.code(), Some(1));
+    assert!(stderr(&missing).contains("LYAGUSHKA_MIN_CLUSTER_SIZE"), "{}", stderr(&missing));
+}
+
+#[test]
+fn clamp_z_caps_the_reported_scores() {
+    let input: &str = "1\n2\n3\n4\n50\n52\n54\n56\n200\n";
+    let clamped: Vec<serde_json::Value> = anomalies(&run(&["--clamp-z", "0.5", "1.5", "3"], input));
+    assert!(clamped.iter().all(|info: &serde_json::Value| info["z_score"].as_f64().is_some_and(|z: f64| z.abs() <= 0.5)));
+    assert!(!run(&["--clamp-z", "-1", "1.5", "3"], input).status.success());
+}
+
+#[test]
+fn jsonl_input_reads_numbers_and_objects() {
+    let expected: Vec<serde_json::Value> = anomalies(&run(&["1.0", "2"], "1\n2\n3\n50\n51\n52\n"));
+    let numbers: &str = "1\n2\n3\n50\n51\n52\n";
+    assert_eq!(anomalies(&run(&["--jsonl-input", "1.0", "2"], numbers)), expected);
+    let objects: &str = "{\"value\": 1}\n{\"value\": 2, \"tag\": \"a\"}\n3\n{\"value\": 50}\n{\"value\": 51}\n{\"value\": 52}\n";
+    assert_eq!(anomalies(&run(&["--jsonl-input", "1.0", "2"], objects)), expected);
+    let named: &str = "{\"t\": 1}\n{\"t\": 2}\n{\"t\": 3}\n{\"t\": 50}\n{\"t\": 51}\n{\"t\": 52}\n";
+    assert_eq!(anomalies(&run(&["--jsonl-field", "t", "1.0", "2"], named)), expected);
+
+    let streamed: Output = run(&["--jsonl-input", "--output", "ndjson", "1.0", "2"], objects);
+    assert!(streamed.status.success(), "{}", stderr(&streamed));
+    let lines: Vec<serde_json::Value> = stdout(&streamed).lines()
+        .map(|line: &str| serde_json::from_str(line).expect("every line is a JSON object"))
+        .collect();
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn round_centroid_emits_integer_centroids() {
+    let input: &str = "1\n2\n3\n4\n50\n51\n52\n";
+    let rounded: Vec<serde_json::Value> = anomalies(&run(&["--round-centroid", "floor", "1.0", "2"], input));
+    assert_eq!(rounded[0]["centroid"], serde_json::json!(2));
+    assert!(rounded[0]["z_score"].is_f64());
+    assert!(!run(&["--round-centroid", "up", "1.0", "2"], input).status.success());
+}
+
+#[test]
+fn time_unit_formats_spans_as_durations() {
+    let input: &str = "0\n1\n2\n3\n3603\n3604\n3605\n3606\n";
+    let timed: Vec<serde_json::Value> = anomalies(&run(&["--time-unit", "s", "1.5", "3"], input));
+    assert_eq!(timed[1]["span_length"], 3600);
+    assert_eq!(timed[1]["duration"], "1h");
+    assert!(anomalies(&run(&["1.5", "3"], input))[1].get("duration").is_none());
+}
+
+#[test]
+fn masks_remove_gaps_and_can_be_repeated() {
+    let input: String = [0, 100, 1000, 1100].iter()
+        .flat_map(|start: &i32| *start..start + 10)
+        .map(|value: i32| format!("{}\n", value))
+        .collect();
+    let spans = |args: &[&str]| -> Vec<u64> {
+        anomalies(&run(args, &input)).iter()
+            .filter(|info: &&serde_json::Value| info["num_elements"] == 0)
+            .map(|info: &serde_json::Value| info["span_length"].as_u64().expect("gaps have a span"))
+            .collect()
+    };
+    assert_eq!(spans(&["1.5", "3"]), [91, 891, 91]);
+    assert_eq!(spans(&["--mask", "200:900", "1.5", "3"]), [91, 91]);
+    assert_eq!(spans(&["--mask", "200:900", "--mask", "20:30", "1.5", "3"]), [91]);
+}
+
+#[test]
+fn bin_edges_come_from_a_list_or_a_file() {
+    let input: &str = "1\n2\n3\n4\n5\n12\n30\n31\n60\n";
+    let counts = |output: &Output| -> Vec<u64> {
+        anomalies(output).iter().map(|bin: &serde_json::Value| bin["count"].as_u64().expect("bins have a count")).collect()
+    };
+    let listed: Output = run(&["--bin-edges", "0,5,10,40,100", "1.5", "3"], input);
+    assert_eq!(counts(&listed), [4, 1, 3, 1]);
+    let path: String = input_file("bin_edges_come_from_a_list_or_a_file", "edges.txt", "0\n5\n10\n40\n100\n");
+    assert_eq!(counts(&run(&["--bin-edges", &path, "1.5", "3"], input)), [4, 1, 3, 1]);
+    assert!(!run(&["--bin-edges", "5", "1.5", "3"], input).status.success());
+}
+
+#[test]
+fn profile_has_one_point_per_position() {
+    let profile: Vec<serde_json::Value> = anomalies(&run(&["--profile", "5", "1.5", "3"], "0\n1\n2\n3\n100\n"));
+    assert_eq!(profile.len(), 5);
+    assert_eq!(profile[0]["position"], 0.0);
+    assert_eq!(profile[4]["position"], 100.0);
+    assert!(profile[0]["density"].as_f64() > profile[2]["density"].as_f64());
+}
+
+#[test]
+fn pretty_indent_sets_the_indent_width() {
+    let input: &str = "1\n2\n3\n50\n51\n52\n";
+    assert!(stdout(&run(&["--pretty-indent", "4", "1.0", "2"], input)).starts_with("[\n    {\n        \""));
+    assert!(stdout(&run(&["--pretty-indent", "tab", "1.0", "2"], input)).starts_with("[\n\t{\n\t\t\""));
+    assert!(stdout(&run(&["1.0", "2"], input)).starts_with("[\n  {\n    \""));
+    assert!(!run(&["--pretty-indent", "wide", "1.0", "2"], input).status.success());
+}
+
+#[test]
+fn max_passes_is_reported_when_it_cuts_the_sweep_short() {
+    let input: &str = "1\n2\n3\n4\n50\n52\n54\n56\n200\n";
+    let summary = |args: &[&str]| -> serde_json::Value {
+        let output: Output = run(args, input);
+        assert!(output.status.success(), "{}", stderr(&output));
+        let report: serde_json::Value = serde_json::from_str(&stdout(&output)).expect("the output is a JSON report");
+        report["summary"].clone()
+    };
+    let capped: serde_json::Value = summary(&["--stability", "0.2", "--max-passes", "2", "--output", "report", "1.5", "3"]);
+    assert_eq!((&capped["stability_passes"], &capped["pass_limit_reached"]), (&serde_json::json!(2), &serde_json::json!(true)));
+    let full: serde_json::Value = summary(&["--stability", "0.2", "--output", "report", "1.5", "3"]);
+    assert_eq!(full["stability_passes"], 5);
+    assert!(full.get("pass_limit_reached").is_none());
+    assert!(!run(&["--timeout", "-1", "1.5", "3"], input).status.success());
+}
+
+#[test]
+fn explain_params_prints_the_resolved_configuration() {
+    let input: &str = "1\n2\n3\n50\n51\n52\n";
+    let output: Output = run(&["--explain-params", "1.5", "3"], input);
+    let params: serde_json::Value = serde_json::from_str(&stderr(&output)).expect("the parameters are JSON");
+    assert_eq!(params["parameters"]["factor"], 1.5);
+    assert_eq!(params["parameters"]["min_cluster_size"], 3);
+    assert_eq!(params["parameters"]["alpha"], 0.05);
+    // The mean spacing is 10.2, so the thresholds are 10.2 / 1.5 and 10.2 * 1.5.
+    assert!((params["mean_distance"].as_f64().unwrap() - 10.2).abs() < 1e-5);
+    assert!((params["cluster_threshold"].as_f64().unwrap() - 6.8).abs() < 1e-5);
+    assert!((params["gap_threshold"].as_f64().unwrap() - 15.3).abs() < 1e-5);
+    // The analysis itself proceeds as without the flag.
+    assert_eq!(anomalies(&output), anomalies(&run(&["1.5", "3"], input)));
+}
+
+#[test]
+fn two_scale_outputs_nested_groups() {
+    // Three super-groups of three tight clusters each, and one lone tight cluster.
+    let input: String = [0, 5000, 10000].iter()
+        .flat_map(|group: &i32| [0, 200, 400].map(|cluster: i32| group + cluster))
+        .chain([30000])
+        .flat_map(|start: i32| start..start + 10)
+        .map(|value: i32| format!("{}\n", value))
+        .collect();
+    let output: Output = run(&["--two-scale", "1.5", "2.0", "5"], &input);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let nested: serde_json::Value = serde_json::from_str(&stdout(&output)).expect("the output is JSON");
+    let groups: Vec<(i64, i64, usize)> = nested["groups"].as_array().unwrap().iter()
+        .map(|group: &serde_json::Value| (group["start"].as_i64().unwrap(), group["end"].as_i64().unwrap(), group["clusters"].as_array().unwrap().len()))
+        .collect();
+    assert_eq!(groups, [(0, 409, 3), (5000, 5409, 3), (10000, 10409, 3)]);
+    assert_eq!(nested["ungrouped"].as_array().unwrap().len(), 1);
+    assert_eq!(nested["ungrouped"][0]["start"], 30000);
+}
+
+#[test]
+fn strict_input_may_hold_comments_and_blank_lines() {
+    let output: Output = run(&["--strict", "1.5", "2"], "# header\n1\n2\n3\n\n# separator\n\n50\n51\n52\n");
+    assert_eq!(anomalies(&output), anomalies(&run(&["1.5", "2"], "1\n2\n3\n50\n51\n52\n")));
+
+    let custom: Output = run(&["--strict", "--comment-prefix", "//", "1.5", "2"], "// header\n1\n2\n3\n\n50\n51\n52\n");
+    assert!(custom.status.success(), "{}", stderr(&custom));
+    let rejected: Output = run(&["--strict", "--comment-prefix", "//", "1.5", "2"], "# header\n1\n2\n");
+    assert_eq!(rejected.status.code(), Some(1));
+    assert!(stderr(&rejected).contains("Line 1: # header is not a number"), "{}", stderr(&rejected));
+}
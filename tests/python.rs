@@ -0,0 +1,39 @@
+//! Runs the Python tests in `tests/python` against the extension module, built with cargo.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[test]
+fn python_tests_pass() {
+    if Command::new("python3").arg("--version").output().is_err() {
+        eprintln!("Skipping the Python tests: python3 is not installed");
+        return;
+    }
+    // Elsewhere, extension modules only link with the flags Maturin passes.
+    if !cfg!(target_os = "linux") {
+        eprintln!("Skipping the Python tests: the extension module is built with Maturin on this platform");
+        return;
+    }
+    let manifest_dir: &Path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // A separate target directory avoids waiting on the lock of the running test build.
+    let target_dir: PathBuf = Path::new(env!("CARGO_TARGET_TMPDIR")).join("python");
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--lib", "--manifest-path"])
+        .arg(manifest_dir.join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", &target_dir)
+        .status()
+        .expect("cargo runs");
+    assert!(status.success(), "the extension module does not build");
+
+    // Python imports the module by its own name rather than that of the library file.
+    let module_dir: PathBuf = target_dir.join("module");
+    std::fs::create_dir_all(&module_dir).expect("the module directory is created");
+    std::fs::copy(target_dir.join("debug/liblyagushka.so"), module_dir.join("lyagushka.so")).expect("the module is copied");
+    let status = Command::new("python3")
+        .args(["-m", "unittest", "discover", "--verbose", "--start-directory"])
+        .arg(manifest_dir.join("tests/python"))
+        .env("PYTHONPATH", &module_dir)
+        .status()
+        .expect("python3 runs");
+    assert!(status.success(), "the Python tests fail");
+}
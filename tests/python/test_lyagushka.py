@@ -0,0 +1,90 @@
+"""Tests of the Python interface of the extension module."""
+
+import json
+import unittest
+
+import lyagushka
+
+
+class IterationTest(unittest.TestCase):
+    def test_iterates_over_the_anomalies_of_the_last_search(self):
+        zhaba = lyagushka.Lyagushka([1, 2, 3, 50, 51, 52])
+        self.assertEqual(list(zhaba), [])
+
+        zhaba.search(1.0, 2)
+        anomalies = [anomaly for anomaly in zhaba]
+        self.assertEqual([(a.start, a.end) for a in anomalies], [(1, 3), (3, 50), (50, 52)])
+        self.assertEqual([a.kind() for a in anomalies], [lyagushka.AnomalyKind.Cluster, lyagushka.AnomalyKind.Gap, lyagushka.AnomalyKind.Cluster])
+        self.assertEqual(anomalies[0].elements, [1, 2, 3])
+        # Every iteration starts over.
+        self.assertEqual(len(list(zhaba)), 3)
+
+
+class DatasetTest(unittest.TestCase):
+    def test_dataset_is_sorted_after_a_search(self):
+        zhaba = lyagushka.Lyagushka([52, 3, 50, 1, 51, 2])
+        zhaba.search(1.0, 2)
+        self.assertEqual(zhaba.dataset, [1, 2, 3, 50, 51, 52])
+
+    def test_range_queries_include_both_bounds(self):
+        zhaba = lyagushka.Lyagushka([9, 1, 5, 5, 3, 7])
+        zhaba.search(1.0, 2)
+        self.assertEqual(zhaba.count_in_range(3, 7), 4)
+        self.assertEqual(zhaba.points_in_range(3, 7), [3, 5, 5, 7])
+        self.assertEqual(zhaba.points_in_range(7, 3), [])
+
+
+class SummaryTest(unittest.TestCase):
+    def test_last_summary_matches_the_report(self):
+        zhaba = lyagushka.Lyagushka([1, 2, 3, 50, 51, 52, 90])
+        self.assertIsNone(zhaba.last_summary())
+        zhaba.search(1.5, 3)
+        summary = zhaba.last_summary()
+        reported = json.loads(zhaba.to_report())["summary"]
+        self.assertEqual(summary.num_points, reported["num_points"])
+        self.assertEqual((summary.num_clusters, summary.num_gaps), (reported["num_clusters"], reported["num_gaps"]))
+        for name in ["mean_distance", "cluster_threshold", "gap_threshold"]:
+            self.assertAlmostEqual(getattr(summary, name), reported[name], places=5)
+
+
+class SearchConfigTest(unittest.TestCase):
+    def test_validation_rejects_out_of_range_options(self):
+        config = lyagushka.SearchConfig(1.5, 3)
+        config.validate()
+        config.sample = 0.0
+        with self.assertRaisesRegex(ValueError, "sample"):
+            config.validate()
+
+
+class BatchTest(unittest.TestCase):
+    def test_batch_matches_analyzing_every_series(self):
+        datasets = [[1, 2, 3, 50, 51, 52], [], [5, 1, 90, 3, 2, 88, 89]]
+        single = [lyagushka.Lyagushka(dataset).search(1.0, 2) for dataset in datasets]
+        self.assertEqual(lyagushka.analyze_batch(datasets, 1.0, 2), single)
+
+
+class ColumnsTest(unittest.TestCase):
+    def test_columns_hold_one_entry_per_anomaly(self):
+        zhaba = lyagushka.Lyagushka([1, 2, 3, 50, 51, 52, 200, 201, 202])
+        columns = zhaba.to_columns(1.0, 2)
+        anomalies = list(zhaba)
+        self.assertEqual({len(column) for column in columns.values()}, {len(anomalies)})
+        self.assertEqual(columns["start"], [a.start for a in anomalies])
+        self.assertEqual(columns["end"], [a.end for a in anomalies])
+        self.assertEqual(columns["elements"][0], [1, 2, 3])
+        for z_score in columns["z_score"]:
+            self.assertTrue(z_score is None or isinstance(z_score, float))
+
+    def test_columns_build_a_data_frame(self):
+        try:
+            import pandas
+        except ImportError:
+            self.skipTest("pandas is not installed")
+        zhaba = lyagushka.Lyagushka([1, 2, 3, 50, 51, 52, 200, 201, 202])
+        frame = pandas.DataFrame(zhaba.to_columns(1.0, 2))
+        self.assertEqual(len(frame), len(list(zhaba)))
+        self.assertEqual(list(frame["start"]), [a.start for a in zhaba])
+
+
+if __name__ == "__main__":
+    unittest.main()